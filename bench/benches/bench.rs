@@ -34,6 +34,28 @@ fn bench_build_ipadic(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_build_target_blocks_ipadic(c: &mut Criterion) {
+    let keyset = load_ipadic();
+
+    let mut group = c.benchmark_group("build/target_blocks/ipadic");
+    group.sample_size(BUILD_SAMPLE_SIZE);
+    group.warm_up_time(BUILD_WARM_UP_TIME);
+    group.measurement_time(BUILD_MEASURE_TIME);
+    group.sampling_mode(SamplingMode::Flat);
+
+    for num_target_blocks in [4, 16, 64].iter() {
+        group.bench_function(format!("yada/{}", num_target_blocks), |b| {
+            b.iter(|| {
+                DoubleArrayBuilder::new()
+                    .with_target_blocks(*num_target_blocks)
+                    .build_from_keyset(keyset.as_slice())
+            });
+        });
+    }
+
+    group.finish();
+}
+
 fn bench_build_unidic(c: &mut Criterion) {
     let keyset = load_unidic();
 
@@ -215,6 +237,18 @@ fn add_search_bench_functions(
             }
         });
     });
+    group.bench_function("fold_common_prefix", |b| {
+        let da_bytes = DoubleArrayBuilder::build(keyset_build.as_slice()).unwrap();
+        let da = DoubleArray::new(da_bytes);
+        b.iter(|| {
+            for (key, _) in keyset_search.as_slice() {
+                let num_matches = da.fold_common_prefix(key, 0, |n, _, _| n + 1);
+                if num_matches < 1 {
+                    panic!();
+                }
+            }
+        });
+    });
 }
 
 fn load_ipadic() -> Vec<(String, u32)> {
@@ -246,6 +280,7 @@ fn load_dic(path: &str) -> Vec<(String, u32)> {
 criterion_group!(
     benches,
     bench_build_ipadic,
+    bench_build_target_blocks_ipadic,
     bench_build_unidic,
     bench_build_kodic,
     bench_search_sorted_ipadic,
@@ -0,0 +1,29 @@
+extern crate yada;
+
+use std::sync::Arc;
+use std::thread;
+use yada::builder::DoubleArrayBuilder;
+use yada::DoubleArray;
+
+const KEYSET: &[(&str, u32)] = &[("a", 0), ("ab", 1), ("abc", 2), ("b", 3)];
+
+fn main() {
+    // searches take `&self`, so one double-array trie can be shared between threads
+    let da = Arc::new(DoubleArray::new(DoubleArrayBuilder::build(KEYSET).unwrap()));
+
+    let handles = (0..4)
+        .map(|_| {
+            let da = Arc::clone(&da);
+            thread::spawn(move || {
+                for (key, value) in KEYSET {
+                    assert_eq!(da.exact_match_search(key), Some(*value));
+                }
+                da.common_prefix_search("abcd").count()
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for handle in handles {
+        assert_eq!(handle.join().unwrap(), 3); // match "a", "ab", "abc"
+    }
+}
@@ -0,0 +1,125 @@
+//! Helpers to store keys of a large alphabet, such as UTF-16 code units, in a byte-oriented double
+//! array.
+//!
+//! NULL (`'\0'`) is the terminator of keys by default. If some byte never appears in keys, it can
+//! be the terminator instead by `BuildConfig::terminator()`, so that keys can contain NULL.
+//!
+//! Plain big-endian bytes of `u16` can't be used as keys, because code units like `0x0041` contain
+//! a NULL byte, which is reserved as a terminator. Instead, each `u16` is encoded into bytes
+//! without NULL as follows:
+//!
+//! ```text
+//!   0 <= c < 64770        [c / 255 + 1, c % 255 + 1]              (2 bytes)
+//!   64770 <= c < 65536    [255, d / 255 + 1, d % 255 + 1]         (3 bytes, d = c - 64770)
+//! ```
+//!
+//! The encoding preserves the order, that is, `u16` keys sort the same as their encoded bytes. So a
+//! keyset sorted by `u16` keys stays sorted after encoding, and `entries()` enumerates keys in the
+//! order of `u16` keys.
+
+/// The smallest code unit encoded into 3 bytes.
+const MIN_LONG_CODE: u32 = 254 * 255;
+
+/// Encodes a `key` of `u16` code units into bytes without NULL.
+pub fn encode_u16_key<K>(key: K) -> Vec<u8>
+where
+    K: AsRef<[u16]>,
+{
+    let key = key.as_ref();
+    let mut bytes = Vec::with_capacity(key.len() * 2);
+    for &c in key {
+        let c = c as u32;
+        if c >= MIN_LONG_CODE {
+            bytes.push(255);
+        }
+        let d = c % MIN_LONG_CODE;
+        bytes.push((d / 255 + 1) as u8);
+        bytes.push((d % 255 + 1) as u8);
+    }
+    bytes
+}
+
+/// Encodes keys of a `keyset` by `encode_u16_key()`. The result can be passed to
+/// `DoubleArrayBuilder::build()`, and it's sorted if the `keyset` is sorted.
+pub fn encode_u16_keys<K>(keyset: &[(K, u32)]) -> Vec<(Vec<u8>, u32)>
+where
+    K: AsRef<[u16]>,
+{
+    keyset
+        .iter()
+        .map(|(key, value)| (encode_u16_key(key), *value))
+        .collect()
+}
+
+/// Decodes bytes encoded by `encode_u16_key()` into `u16` code units. Returns `None` if the
+/// `bytes` are not a valid encoding.
+pub fn decode_u16_key(bytes: &[u8]) -> Option<Vec<u16>> {
+    let mut key = Vec::with_capacity(bytes.len() / 2);
+    let mut iter = bytes.iter();
+    while let Some(&b) = iter.next() {
+        let (base, hi) = if b == 255 {
+            (MIN_LONG_CODE, *iter.next()?)
+        } else {
+            (0, b)
+        };
+        let lo = *iter.next()?;
+        if hi == 0 || lo == 0 {
+            return None;
+        }
+        let c = base + (hi as u32 - 1) * 255 + (lo as u32 - 1);
+        if c > u16::MAX as u32 {
+            return None;
+        }
+        key.push(c as u16);
+    }
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::alphabet::{decode_u16_key, encode_u16_key, encode_u16_keys};
+    use crate::builder::DoubleArrayBuilder;
+    use crate::DoubleArray;
+
+    #[test]
+    fn test_encode_decode() {
+        let codes = [0u16, 1, 0x41, 0xFF, 0x100, 64769, 64770, 65535];
+        for &c in &codes {
+            let bytes = encode_u16_key([c]);
+            assert!(!bytes.contains(&0));
+            assert_eq!(decode_u16_key(&bytes), Some(vec![c]));
+        }
+        // the order is preserved
+        for w in codes.windows(2) {
+            assert!(encode_u16_key(&w[..1]) < encode_u16_key(&w[1..]));
+        }
+
+        assert_eq!(decode_u16_key(&[1]), None);
+        assert_eq!(decode_u16_key(&[255, 1]), None);
+        assert_eq!(decode_u16_key(&[0, 1]), None);
+        assert_eq!(decode_u16_key(&[255, 255, 255]), None);
+    }
+
+    #[test]
+    fn test_build_u16_keys() {
+        let keyset: &[(&[u16], u32)] = &[
+            (&[0x41], 0),
+            (&[0x41, 0x3042], 1),
+            (&[0x100], 2),
+            (&[0xFFFF, 0], 3),
+        ];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(&encode_u16_keys(keyset)).unwrap());
+        for (key, value) in keyset {
+            assert_eq!(da.exact_match_search(encode_u16_key(key)), Some(*value));
+        }
+        assert_eq!(
+            da.entries()
+                .map(|(key, value)| (decode_u16_key(&key).unwrap(), value))
+                .collect::<Vec<_>>(),
+            keyset
+                .iter()
+                .map(|(key, value)| (key.to_vec(), *value))
+                .collect::<Vec<_>>()
+        );
+    }
+}
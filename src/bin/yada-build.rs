@@ -0,0 +1,55 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process;
+use yada::builder::DoubleArrayBuilder;
+use yada::DoubleArray;
+
+/// Build a double-array trie from a TSV which contains byte-sorted keys and values, and write
+/// it to the output file. Stats of the double-array are reported on stderr.
+///
+/// # Usage
+///
+/// ```bash
+/// $ cargo run --features cli --bin yada-build -- output.da input.tsv
+/// ```
+///
+/// or read the TSV from stdin:
+///
+/// ```bash
+/// $ cat input.tsv | cargo run --features cli --bin yada-build -- output.da
+/// ```
+fn main() {
+    if let Err(message) = run() {
+        eprintln!("yada-build: {}", message);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let args = std::env::args().collect::<Vec<_>>();
+    let (output, input) = match args.len() {
+        2 => (&args[1], None),
+        3 => (&args[1], Some(&args[2])),
+        _ => return Err("usage: yada-build <OUTPUT> [INPUT]".to_string()),
+    };
+
+    let reader: Box<dyn BufRead> = match input {
+        Some(path) if path != "-" => Box::new(BufReader::new(
+            File::open(path).map_err(|e| format!("{}: {}", path, e))?,
+        )),
+        _ => Box::new(BufReader::new(io::stdin())),
+    };
+    let da_bytes = DoubleArrayBuilder::build_from_tsv_reader(reader).map_err(|e| e.to_string())?;
+
+    let mut file = File::create(output).map_err(|e| format!("{}: {}", output, e))?;
+    file.write_all(&da_bytes)
+        .map_err(|e| format!("{}: {}", output, e))?;
+
+    let stats = DoubleArray::new(da_bytes.as_slice()).stats();
+    eprintln!("keys: {}", stats.num_leaves);
+    eprintln!("bytes: {}", da_bytes.len());
+    eprintln!("units: {}", stats.num_units);
+    eprintln!("max depth: {}", stats.max_depth);
+
+    Ok(())
+}
@@ -1,475 +1,2535 @@
-use crate::unit::{Unit, UnitID};
-use std::collections::HashSet;
+use crate::unit::{Unit, UnitID, UNIT_SIZE};
+use crate::wide::{Unit64, WIDE_HEADER, WIDE_UNIT_SIZE};
+use crate::{DoubleArray, DoubleArrayError};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{BuildHasherDefault, Hasher};
+use std::io::{self, BufRead, Write};
 
-const BLOCK_SIZE: usize = 256;
-const NUM_TARGET_BLOCKS: i32 = 16; // the number of target blocks to find offsets
+/// The number of bits of positions in a block. The size of blocks is fixed to the number of
+/// labels (256), because children of a node are placed at `offset ^ label`, which spans exactly
+/// one aligned range of 256 units, and positions in a block are addressed by `u8`.
+const BLOCK_BITS: u32 = 8;
+const BLOCK_SIZE: usize = 1 << BLOCK_BITS;
+
+// A mismatched array size here means that BLOCK_SIZE differs from the number of labels (256),
+// which the layout of offsets requires.
+const _: [(); 1] = [(); (BLOCK_SIZE == u8::MAX as usize + 1) as usize]; // BLOCK_SIZE must be 256
+const NUM_TARGET_BLOCKS: usize = 16; // the default number of target blocks to find offsets
 const INVALID_NEXT: u8 = 0; // 0 means that there is no next unused unit
 const INVALID_PREV: u8 = 255; // 255 means that there is no previous unused unit
+const MAX_BLOCKS: usize = 1 << 24; // offsets in a builder are 32 bits
+const MIN_DENSE_LABELS: usize = 8; // labels are checked in bit-parallel if there are more labels
+const ESTIMATED_KEYS_PER_BLOCK: usize = 64; // about 55-70 keys per block for ipadic/unidic/kodic
 
-/// A double-array trie builder.
-#[derive(Debug)]
-pub struct DoubleArrayBuilder {
-    pub blocks: Vec<DoubleArrayBlock>,
-    pub used_offsets: HashSet<u32>,
+/// Returns the limit of offsets, which must be represented as 29 bits integer.
+#[cfg(not(test))]
+fn offset_limit() -> u32 {
+    1 << 29
 }
 
-impl DoubleArrayBuilder {
-    /// Constructs a new `DoubleArrayBuilder` with an empty `DoubleArrayBlock`.
-    pub fn new() -> Self {
-        Self {
-            blocks: vec![DoubleArrayBlock::new(0)],
-            used_offsets: HashSet::new(),
+// tests lower the limit of offsets to reach it without allocating a huge double-array
+#[cfg(test)]
+thread_local! {
+    static OFFSET_LIMIT: std::cell::Cell<u32> = const { std::cell::Cell::new(1 << 29) };
+}
+
+#[cfg(test)]
+fn offset_limit() -> u32 {
+    OFFSET_LIMIT.with(|limit| limit.get())
+}
+
+/// An error that occurs while building a double-array trie.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    /// The key at `index` of the keyset contains a NULL character ('\0') that is reserved as a
+    /// terminator, or the terminator set by `BuildConfig::terminator()`. `position` is the byte
+    /// offset of the terminator in the key. `index` is `None` if the key is inserted by
+    /// `DoubleArrayBuilder::insert()`.
+    NullCharacter {
+        index: Option<usize>,
+        position: usize,
+    },
+    /// The key at `index` of the keyset has already been inserted. `position` is the byte offset
+    /// where the key ends, that is, the length of the key. `index` is `None` if the key is
+    /// inserted by `DoubleArrayBuilder::insert()`.
+    DuplicateKey {
+        index: Option<usize>,
+        position: usize,
+    },
+    /// The keys are not sorted by the byte order. The key at `index` of the keyset is out of order.
+    UnsortedKeys { index: usize },
+    /// The value of the key at `index` of the keyset can't be represented as a 31 bits unsigned
+    /// integer. `index` is `None` if the key is inserted by `DoubleArrayBuilder::insert()`.
+    ValueTooLarge { index: Option<usize>, value: u32 },
+    /// The offset can't be represented as a 29 bits unsigned integer.
+    OffsetOverflow,
+    /// The number of blocks exceeds the limit.
+    BlockLimitExceeded,
+    /// The line of an input is not in the `key\tvalue` format. `line` is 1-origin.
+    InvalidLine { line: usize },
+    /// Reading an input failed.
+    Io(io::ErrorKind),
+}
+
+impl std::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BuildError::NullCharacter { index, position } => write!(
+                f,
+                "{} contains a NULL character at byte {}",
+                key_name(*index),
+                position
+            ),
+            BuildError::DuplicateKey { index, .. } => {
+                write!(f, "{} has already been inserted", key_name(*index))
+            }
+            BuildError::UnsortedKeys { index } => {
+                write!(f, "keys are not sorted at key {}", index)
+            }
+            BuildError::ValueTooLarge { index, value } => write!(
+                f,
+                "value {} of {} can't be represented as 31 bits",
+                value,
+                key_name(*index)
+            ),
+            BuildError::OffsetOverflow => write!(f, "offset can't be represented as 29 bits"),
+            BuildError::BlockLimitExceeded => write!(f, "number of blocks exceeds the limit"),
+            BuildError::InvalidLine { line } => {
+                write!(f, "line {}: expected `key\\tvalue`", line)
+            }
+            BuildError::Io(kind) => write!(f, "failed to read input: {:?}", kind),
         }
     }
+}
 
-    /// Builds a double-array trie with a `keyset` and returns it when build finished successfully.
-    /// Otherwise, returns `None`.
-    /// The `keyset` must be sorted.
-    pub fn build<'a, T>(keyset: &[(T, u32)]) -> Option<Vec<u8>>
-    where
-        T: AsRef<[u8]>,
-    {
-        Self::new().build_from_keyset(keyset)
+/// Names the key at `index` of the keyset in error messages.
+fn key_name(index: Option<usize>) -> String {
+    match index {
+        Some(index) => format!("key {}", index),
+        None => "key".to_string(),
     }
+}
 
-    /// Builds a double-array trie with a `keyset` and returns it when build finished successfully.
-    /// Otherwise, returns `None`.
-    /// The `keyset` must be sorted.
-    pub fn build_from_keyset<T>(&mut self, keyset: &[(T, u32)]) -> Option<Vec<u8>>
-    where
-        T: AsRef<[u8]>,
-    {
-        self.reserve(0); // reserve root node
-        self.build_recursive(keyset, 0, 0, keyset.len(), 0)?;
+impl BuildError {
+    /// Returns the index of the key which caused the error if any.
+    fn index(&self) -> Option<usize> {
+        match *self {
+            BuildError::NullCharacter { index, .. }
+            | BuildError::DuplicateKey { index, .. }
+            | BuildError::ValueTooLarge { index, .. } => index,
+            BuildError::UnsortedKeys { index } => Some(index),
+            _ => None,
+        }
+    }
 
-        let mut da_bytes = Vec::with_capacity(self.blocks.len() * BLOCK_SIZE);
-        for block in &self.blocks {
-            for unit in block.units.iter() {
-                let bytes = unit.as_u32().to_le_bytes();
-                da_bytes.extend_from_slice(&bytes);
-            }
+    /// Replaces the index of the key which caused the error.
+    fn with_index(self, index: usize) -> Self {
+        match self {
+            BuildError::NullCharacter { position, .. } => BuildError::NullCharacter {
+                index: Some(index),
+                position,
+            },
+            BuildError::DuplicateKey { position, .. } => BuildError::DuplicateKey {
+                index: Some(index),
+                position,
+            },
+            BuildError::UnsortedKeys { .. } => BuildError::UnsortedKeys { index },
+            BuildError::ValueTooLarge { value, .. } => BuildError::ValueTooLarge {
+                index: Some(index),
+                value,
+            },
+            err => err,
         }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Statistics of a double-array trie under construction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildStats {
+    /// The number of `Unit`s.
+    pub num_units: u32,
+    /// The number of used `Unit`s.
+    pub num_used_units: u32,
+    /// The number of `DoubleArrayBlock`s.
+    pub num_blocks: usize,
+    /// The ratio of used `Unit`s to all `Unit`s.
+    pub fill_rate: f64,
+    /// The number of bytes saved by dropping trailing unused blocks.
+    pub num_shrunk_bytes: usize,
+}
+
+/// A configuration of builds, which is constructed by `DoubleArrayBuilder::builder()`.
+///
+/// ```
+/// use yada::builder::DoubleArrayBuilder;
+/// use yada::DoubleArray;
+///
+/// let keyset = &[("b", 1), ("a", 0), ("b", 2)];
+/// let da_bytes = DoubleArrayBuilder::builder()
+///     .target_blocks(4)
+///     .sort_input(true)
+///     .check_duplicates(false)
+///     .build(keyset)
+///     .unwrap();
+/// let da = DoubleArray::new(da_bytes);
+/// assert_eq!(da.exact_match_search("a"), Some(0));
+/// assert_eq!(da.exact_match_search("b"), Some(2));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildConfig {
+    num_target_blocks: usize,
+    max_blocks: usize,
+    sort_input: bool,
+    check_duplicates: bool,
+    dedup: bool,
+    terminator: u8,
+}
 
-        Some(da_bytes)
+impl Default for BuildConfig {
+    fn default() -> Self {
+        Self {
+            num_target_blocks: NUM_TARGET_BLOCKS,
+            max_blocks: usize::MAX,
+            sort_input: false,
+            check_duplicates: true,
+            dedup: false,
+            terminator: 0,
+        }
     }
+}
 
-    /// Returns the number of `Unit`s that this builder contains.
-    pub fn num_units(&self) -> u32 {
-        (self.blocks.len() * BLOCK_SIZE) as u32
+impl BuildConfig {
+    /// Sets the number of last blocks in which offsets of nodes are searched (16 by default).
+    /// See `DoubleArrayBuilder::with_target_blocks()`.
+    pub fn target_blocks(mut self, num_target_blocks: usize) -> Self {
+        self.num_target_blocks = num_target_blocks;
+        self
     }
 
-    /// Returns the number of used `Unit`s that this builder contains.
-    pub fn num_used_units(&self) -> u32 {
-        self.blocks
-            .iter()
-            .map(|block| {
-                block
-                    .is_used
-                    .iter()
-                    .fold(0, |acc, &is_used| acc + if is_used { 1 } else { 0 })
-            })
-            .sum::<u32>()
+    /// Sets the maximum number of blocks (unlimited by default).
+    /// See `DoubleArrayBuilder::with_max_blocks()`.
+    pub fn max_blocks(mut self, max_blocks: usize) -> Self {
+        self.max_blocks = max_blocks;
+        self
     }
 
-    fn get_block(&self, unit_id: UnitID) -> Option<&DoubleArrayBlock> {
-        self.blocks.get(unit_id / BLOCK_SIZE)
+    /// Sorts the keyset by the byte order of keys before building if `sort_input` is true (false
+    /// by default). Indices in errors are then positions in the sorted keyset.
+    pub fn sort_input(mut self, sort_input: bool) -> Self {
+        self.sort_input = sort_input;
+        self
     }
 
-    fn get_block_mut(&mut self, unit_id: UnitID) -> Option<&mut DoubleArrayBlock> {
-        self.blocks.get_mut(unit_id / BLOCK_SIZE)
+    /// Fails with `BuildError::DuplicateKey` on adjacent duplicated keys if `check_duplicates` is
+    /// true (by default). Otherwise, the last value of duplicated keys is kept like inserting into
+    /// a map.
+    pub fn check_duplicates(mut self, check_duplicates: bool) -> Self {
+        self.check_duplicates = check_duplicates;
+        self
     }
 
-    fn extend_block(&mut self) -> &DoubleArrayBlock {
-        let block_id = self.blocks.len();
-        self.blocks.push(DoubleArrayBlock::new(block_id));
-        self.blocks.last().unwrap()
+    /// Collapses adjacent duplicated keys with the same value into one if `dedup` is true (false by
+    /// default). See `DoubleArrayBuilder::with_dedup()`.
+    pub fn dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
     }
 
-    fn extend_block_mut(&mut self) -> &mut DoubleArrayBlock {
-        let block_id = self.blocks.len();
-        self.blocks.push(DoubleArrayBlock::new(block_id));
-        self.blocks.last_mut().unwrap()
+    /// Terminates keys by `terminator` instead of NULL (`'\0'`, by default), so that keys can
+    /// contain NULL but not the `terminator`, which is reported as `BuildError::NullCharacter`.
+    /// Each byte of keys is stored as XOR with the `terminator`, so the built double array has to
+    /// be searched by `DoubleArray::with_terminator()` with the same `terminator`.
+    ///
+    /// If the `terminator` is not NULL, the keyset is always sorted after the XOR, and indices in
+    /// errors are positions in the given keyset.
+    pub fn terminator(mut self, terminator: u8) -> Self {
+        self.terminator = terminator;
+        self
     }
 
-    fn get_unit_mut(&mut self, unit_id: UnitID) -> &mut Unit {
-        while self.get_block(unit_id).is_none() {
-            self.extend_block_mut();
-        }
-        let block = self.get_block_mut(unit_id).unwrap();
-        &mut block.units[unit_id % BLOCK_SIZE]
+    /// Builds a double-array trie with a `keyset` and returns it when build finished successfully.
+    /// Otherwise, returns `None`.
+    pub fn build<T>(&self, keyset: &[(T, u32)]) -> Option<Vec<u8>>
+    where
+        T: AsRef<[u8]>,
+    {
+        self.try_build(keyset).ok()
     }
 
-    fn reserve(&mut self, unit_id: UnitID) {
-        while self.get_block(unit_id).is_none() {
-            self.extend_block_mut();
+    /// Builds a double-array trie with a `keyset` and returns it when build finished successfully.
+    /// Otherwise, returns a `BuildError` that describes why the build failed.
+    pub fn try_build<T>(&self, keyset: &[(T, u32)]) -> Result<Vec<u8>, BuildError>
+    where
+        T: AsRef<[u8]>,
+    {
+        let mut builder = DoubleArrayBuilder::new()
+            .with_target_blocks(self.num_target_blocks)
+            .with_max_blocks(self.max_blocks)
+            .with_dedup(self.dedup);
+        if self.terminator != 0 {
+            return self.try_build_terminated(builder, keyset);
         }
-        let block = self.get_block_mut(unit_id).unwrap();
-        assert!(unit_id % BLOCK_SIZE < 256);
-        block.reserve((unit_id % BLOCK_SIZE) as u8);
+        if !self.sort_input && self.check_duplicates {
+            return builder.try_build_from_keyset(keyset);
+        }
+
+        let mut keyset = keyset
+            .iter()
+            .map(|(key, value)| (key.as_ref(), *value))
+            .collect::<Vec<_>>();
+        if self.sort_input {
+            // the sort is stable, so the order of duplicated keys is kept
+            keyset.sort_by(|a, b| a.0.cmp(b.0));
+        }
+        if !self.check_duplicates {
+            dedup_keep_last(&mut keyset);
+        }
+        builder.try_build_from_keyset(&keyset)
     }
 
-    fn build_recursive<T>(
-        &mut self,
+    /// Builds a `keyset` whose keys are terminated by `self.terminator`. The XOR with the
+    /// terminator maps it to 0, which is the label of leaves, and NULL to an ordinary label, but
+    /// doesn't preserve the order of keys.
+    fn try_build_terminated<T>(
+        &self,
+        mut builder: DoubleArrayBuilder,
         keyset: &[(T, u32)],
-        depth: usize,
-        begin: usize,
-        end: usize,
-        unit_id: UnitID,
-    ) -> Option<()>
+    ) -> Result<Vec<u8>, BuildError>
     where
         T: AsRef<[u8]>,
     {
-        // element of labels is a tuple (label, start_position, end_position)
-        let mut labels: Vec<(u8, usize, usize)> = Vec::with_capacity(256);
-        let mut value = None;
-
-        for i in begin..end {
-            let key_value = keyset.get(i).unwrap();
-            let label = {
-                let key = key_value.0.as_ref();
-                if depth == key.len() {
-                    0
-                } else {
-                    *key.get(depth)?
-                }
-            };
-            if label == 0 {
-                assert!(value.is_none()); // there is just one '\0' in a key
-                value = Some(key_value.1);
-            }
-            match labels.last_mut() {
-                Some(last_label) => {
-                    if last_label.0 != label {
-                        last_label.2 = i; // set end position
-                        labels.push((label, i, 0));
-                    }
-                }
-                None => {
-                    labels.push((label, i, 0));
-                }
+        let terminator = self.terminator;
+        let mut relabeled = Vec::with_capacity(keyset.len());
+        for (index, (key, value)) in keyset.iter().enumerate() {
+            let key = key.as_ref();
+            if let Some(position) = key.iter().position(|&b| b == terminator) {
+                return Err(BuildError::NullCharacter {
+                    index: Some(index),
+                    position,
+                });
             }
+            let key = key.iter().map(|&b| b ^ terminator).collect::<Vec<_>>();
+            relabeled.push((key, *value, index));
+        }
+        // the sort is stable, so the order of duplicated keys is kept
+        relabeled.sort_by(|a, b| a.0.cmp(&b.0));
+        if !self.check_duplicates {
+            relabeled.reverse();
+            relabeled.dedup_by(|a, b| a.0 == b.0);
+            relabeled.reverse();
         }
-        assert!(labels.len() > 0);
 
-        let last_label = labels.last_mut().unwrap();
-        last_label.2 = end;
+        let sorted = relabeled
+            .iter()
+            .map(|(key, value, _)| (key, *value))
+            .collect::<Vec<_>>();
+        builder
+            .try_build_from_keyset(&sorted)
+            .map_err(|e| match e.index() {
+                Some(i) => e.with_index(relabeled[i].2),
+                None => e,
+            })
+    }
+}
 
-        let labels_ = labels.iter().map(|(key, _, _)| *key).collect::<Vec<_>>();
-        assert!(labels_.len() > 0);
+/// Removes adjacent duplicated keys of a `keyset` except the last one.
+fn dedup_keep_last<T>(keyset: &mut Vec<(T, u32)>)
+where
+    T: AsRef<[u8]>,
+{
+    keyset.reverse();
+    keyset.dedup_by(|a, b| a.0.as_ref() == b.0.as_ref());
+    keyset.reverse();
+}
 
-        // search an offset where these children fits to unused positions.
-        let offset: u32 = loop {
-            let offset = self.find_offset(unit_id, &labels_);
-            if offset.is_some() {
-                break offset.unwrap();
-            }
-            self.extend_block();
-        };
-        assert!(
-            offset < (1u32 << 29),
-            "offset must be represented as 29 bits integer"
-        );
+/// A fast hasher for offsets. Offsets are dense integers, so they are just multiplied by a
+/// constant like FxHash instead of hashed by SipHash.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct OffsetHasher(u64);
 
-        // mark the offset used
-        self.used_offsets.insert(offset);
+impl Hasher for OffsetHasher {
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0
+    }
 
-        let has_leaf = labels_.first().filter(|&&x| x == 0).is_some();
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_u64(byte as u64);
+        }
+    }
 
-        // populate offset and has_leaf flag to parent node
-        let parent_unit = self.get_unit_mut(unit_id);
-        assert_eq!(
-            parent_unit.offset(),
-            0,
-            "offset() should return 0 before set_offset()"
-        );
-        parent_unit.set_offset(offset ^ unit_id as u32); // store the relative offset to the index
-        assert!(
-            !parent_unit.has_leaf(),
-            "has_leaf() should return false before set_has_leaf()"
-        );
-        parent_unit.set_has_leaf(has_leaf);
+    #[inline]
+    fn write_u32(&mut self, i: u32) {
+        self.write_u64(i as u64);
+    }
 
-        // populate label or associated value to children node
-        for label in labels_ {
-            let child_id = (offset ^ label as u32) as UnitID;
-            self.reserve(child_id);
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.0 = (self.0.rotate_left(5) ^ i).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+}
 
-            let unit = self.get_unit_mut(child_id);
+/// A set of used offsets.
+pub type OffsetSet = HashSet<u32, BuildHasherDefault<OffsetHasher>>;
 
-            // child node units should be empty
-            assert_eq!(unit.offset(), 0);
-            assert_eq!(unit.label(), 0);
-            assert_eq!(unit.value(), 0);
-            assert!(!unit.has_leaf());
+/// A double-array trie builder.
+#[derive(Debug)]
+pub struct DoubleArrayBuilder {
+    pub blocks: Vec<DoubleArrayBlock>,
+    pub used_offsets: OffsetSet,
+    num_target_blocks: usize,
+    max_blocks: usize,
+    dedup: bool,
+    num_shrunk_bytes: usize,
+    // relative offsets of nodes and values of leaves when building the wide format
+    wide_fields: Option<Vec<u32>>,
+}
 
-            if label == 0 {
-                assert!(value.is_some());
-                unit.set_value(value.unwrap());
-            } else {
-                unit.set_label(label);
-            }
-        }
+impl Default for DoubleArrayBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        // recursive call in depth-first order
-        for (label, begin, end) in labels {
-            self.build_recursive(
-                keyset,
-                depth + 1,
-                begin,
-                end,
-                (label as u32 ^ offset) as UnitID,
-            );
+impl DoubleArrayBuilder {
+    /// Constructs a new `DoubleArrayBuilder` with an empty `DoubleArrayBlock`.
+    pub fn new() -> Self {
+        Self {
+            blocks: vec![DoubleArrayBlock::new(0)],
+            used_offsets: OffsetSet::default(),
+            num_target_blocks: NUM_TARGET_BLOCKS,
+            max_blocks: usize::MAX,
+            dedup: false,
+            num_shrunk_bytes: 0,
+            wide_fields: None,
         }
+    }
 
-        Some(())
+    /// Returns a `BuildConfig` with the default settings to configure a build with a fluent API.
+    /// `build()` and `build_from_keyset()` are shortcuts for common cases.
+    pub fn builder() -> BuildConfig {
+        BuildConfig::default()
     }
 
-    fn find_offset(&self, unit_id: UnitID, labels: &Vec<u8>) -> Option<u32> {
-        let head_block = (self.blocks.len() as i32 - NUM_TARGET_BLOCKS).max(0) as usize;
+    /// Sets the number of last blocks in which offsets of nodes are searched (16 by default).
+    /// Larger values produce denser double-arrays but make builds slower.
+    pub fn with_target_blocks(mut self, num_target_blocks: usize) -> Self {
+        self.num_target_blocks = num_target_blocks.max(1);
+        self
+    }
+
+    /// Reserves memory for blocks in proportion to the estimated number of keys `n` to reduce
+    /// reallocations while building. This is only a hint and doesn't affect the built trie.
+    pub fn with_estimated_keys(mut self, n: usize) -> Self {
+        let num_blocks = n / ESTIMATED_KEYS_PER_BLOCK + 1;
         self.blocks
-            .iter()
-            .skip(head_block) // search for offset in last N blocks
-            .find_map(|block| {
-                // find the first valid offset in a block
-                for offset in block.find_offset(unit_id, labels) {
-                    let offset_u32 = (block.id as u32) << 8 | offset as u32;
-                    if !self.used_offsets.contains(&offset_u32) {
-                        return Some((block.id as u32) << 8 | offset as u32);
-                    }
-                }
-                None
-            })
+            .reserve(num_blocks.saturating_sub(self.blocks.len()));
+        self
     }
-}
 
-const DEFAULT_UNITS: [Unit; BLOCK_SIZE] = [Unit::new(); BLOCK_SIZE];
-const DEFAULT_IS_USED: [bool; BLOCK_SIZE] = [false; BLOCK_SIZE];
-const DEFAULT_NEXT_UNUSED: [u8; BLOCK_SIZE] = {
-    let mut next_unused = [INVALID_NEXT; BLOCK_SIZE];
-    let mut i = 0;
-    while i < next_unused.len() - 1 {
-        next_unused[i] = (i + 1) as u8;
-        i += 1;
+    /// Sets the maximum number of blocks (unlimited by default) to bound the memory usage of
+    /// builds. Builds that need more blocks fail with `BuildError::BlockLimitExceeded`.
+    pub fn with_max_blocks(mut self, max_blocks: usize) -> Self {
+        self.max_blocks = max_blocks;
+        self
     }
-    next_unused
-};
-const DEFAULT_PREV_UNUSED: [u8; BLOCK_SIZE] = {
-    let mut prev_unused = [INVALID_PREV; BLOCK_SIZE];
-    let mut i = 1;
-    while i < prev_unused.len() {
-        prev_unused[i] = (i - 1) as u8;
-        i += 1;
+
+    /// Collapses adjacent duplicated keys of a keyset into one if `dedup` is true and they have
+    /// the same value (false by default). Duplicated keys with different values still fail with
+    /// `BuildError::DuplicateKey`.
+    pub fn with_dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
     }
-    prev_unused
-};
 
-/// A block that have a shard of a double-array and other useful data structures.
-pub struct DoubleArrayBlock {
-    pub id: usize,
-    pub units: [Unit; BLOCK_SIZE],
-    pub is_used: [bool; BLOCK_SIZE],
-    pub head_unused: u8,
-    pub next_unused: [u8; BLOCK_SIZE],
-    pub prev_unused: [u8; BLOCK_SIZE],
-}
+    /// Builds a double-array trie with a `keyset` and returns it when build finished successfully.
+    /// Otherwise, returns `None`.
+    /// The `keyset` must be sorted.
+    pub fn build<T>(keyset: &[(T, u32)]) -> Option<Vec<u8>>
+    where
+        T: AsRef<[u8]>,
+    {
+        Self::try_build(keyset).ok()
+    }
 
-impl DoubleArrayBlock {
-    const fn new(id: usize) -> Self {
-        Self {
-            id,
-            units: DEFAULT_UNITS,
-            is_used: DEFAULT_IS_USED,
-            head_unused: 0,
-            next_unused: DEFAULT_NEXT_UNUSED,
-            prev_unused: DEFAULT_PREV_UNUSED,
-        }
+    /// Builds a double-array trie with a `keyset` and returns it when build finished successfully.
+    /// Otherwise, returns a `BuildError` that describes why the build failed.
+    /// The `keyset` must be sorted.
+    pub fn try_build<T>(keyset: &[(T, u32)]) -> Result<Vec<u8>, BuildError>
+    where
+        T: AsRef<[u8]>,
+    {
+        Self::new().try_build_from_keyset(keyset)
     }
 
-    /// Finds a valid offset in this block.
-    fn find_offset<'a>(
-        &'a self,
-        unit_id: UnitID,
-        labels: &'a Vec<u8>,
-    ) -> impl Iterator<Item = u8> + 'a {
-        assert!(labels.len() > 0);
-        FindOffset {
-            unused_id: self.head_unused,
-            block: self,
-            unit_id,
-            labels,
-        }
+    /// Builds a double-array trie with a `keyset` like `build()`, but returns units without
+    /// serializing them. Use `DoubleArray::from_units()` to search them.
+    /// The `keyset` must be sorted.
+    pub fn build_units<T>(keyset: &[(T, u32)]) -> Option<Vec<Unit>>
+    where
+        T: AsRef<[u8]>,
+    {
+        let mut builder = Self::new();
+        builder.build_blocks(keyset, &mut |_, _| {}).ok()?;
+        Some(builder.units().collect())
     }
 
-    fn reserve(&mut self, id: u8) {
-        // maintain is_used
-        self.is_used[id as usize] = true;
+    /// Builds a double-array trie with a `keyset` like `build()`, but returns units as `u32`s
+    /// without serializing them. Use `DoubleArray::from_u32()` to search them.
+    /// The `keyset` must be sorted.
+    pub fn build_u32<T>(keyset: &[(T, u32)]) -> Option<Vec<u32>>
+    where
+        T: AsRef<[u8]>,
+    {
+        let mut builder = Self::new();
+        builder.build_blocks(keyset, &mut |_, _| {}).ok()?;
+        Some(builder.units().map(|unit| unit.as_u32()).collect())
+    }
 
-        let prev_id = self.prev_unused[id as usize];
-        let next_id = self.next_unused[id as usize];
+    /// Builds a double-array trie with a `keyset` of string keys like `build()`. Keys are treated
+    /// as UTF-8 bytes.
+    /// The `keyset` must be sorted.
+    pub fn build_str(keyset: &[(&str, u32)]) -> Option<Vec<u8>> {
+        Self::build(keyset)
+    }
 
-        // maintain next_unused
-        if prev_id != INVALID_PREV {
-            self.next_unused[prev_id as usize] = next_id;
-        }
-        self.next_unused[id as usize] = INVALID_NEXT; // this line can be removed
+    /// Builds a double-array trie with a `keyset` of signed values and returns it when build
+    /// finished successfully. Otherwise, returns `None`. Values must be in the range of
+    /// `-(1 << 30)..(1 << 30)` because they are stored as 31 bits. Use
+    /// `DoubleArray::exact_match_search_i32()` to search the values.
+    /// The `keyset` must be sorted.
+    pub fn build_i32<T>(keyset: &[(T, i32)]) -> Option<Vec<u8>>
+    where
+        T: AsRef<[u8]>,
+    {
+        let keyset = keyset
+            .iter()
+            .map(|(key, value)| {
+                if (-(1 << 30)..(1 << 30)).contains(value) {
+                    Some((key.as_ref(), *value as u32 & 0x7FFF_FFFF))
+                } else {
+                    None
+                }
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Self::build(&keyset)
+    }
+
+    /// Builds a double-array trie which maps each key of `keys` to its ordinal, that is, its index
+    /// in `keys`, and returns it when build finished successfully. Otherwise, returns `None`.
+    /// Ordinals are dense in `0..keys.len()`, so they can index an external array of exactly
+    /// `keys.len()` elements. Use `DoubleArray::ordinal_of()` to search them.
+    /// The `keys` must be sorted and unique.
+    pub fn build_ordinals<K>(keys: &[K]) -> Option<Vec<u8>>
+    where
+        K: AsRef<[u8]>,
+    {
+        let keyset = keys
+            .iter()
+            .zip(0..)
+            .map(|(key, ordinal)| (key.as_ref(), ordinal))
+            .collect::<Vec<_>>();
+        Self::build(&keyset)
+    }
+
+    /// Builds a double-array trie of the wide format, which has 64-bit units, with a `keyset` and
+    /// returns it when build finished successfully. Otherwise, returns `None`. The built trie can
+    /// be loaded by `DoubleArray64` and isn't limited by the 29 bits offsets and the 31 bits
+    /// values of the 32-bit units.
+    /// The `keyset` must be sorted.
+    pub fn build_wide<T>(keyset: &[(T, u32)]) -> Option<Vec<u8>>
+    where
+        T: AsRef<[u8]>,
+    {
+        Self::try_build_wide(keyset).ok()
+    }
+
+    /// Builds a double-array trie of the wide format like `build_wide()`, and returns a
+    /// `BuildError` that describes why the build failed.
+    /// The `keyset` must be sorted.
+    pub fn try_build_wide<T>(keyset: &[(T, u32)]) -> Result<Vec<u8>, BuildError>
+    where
+        T: AsRef<[u8]>,
+    {
+        let mut builder = Self::new();
+        builder.wide_fields = Some(Vec::new());
+        builder.try_build_from_keyset(keyset)
+    }
+
+    /// Checks that keys of a `keyset` are sorted by the byte order, and returns the first index
+    /// `i` where `keyset[i - 1]` is greater than `keyset[i]` as an error. Adjacent duplicated keys
+    /// are regarded as sorted.
+    pub fn check_sorted<T>(keyset: &[(T, u32)]) -> Result<(), usize>
+    where
+        T: AsRef<[u8]>,
+    {
+        match keyset
+            .windows(2)
+            .position(|pair| pair[0].0.as_ref() > pair[1].0.as_ref())
+        {
+            Some(i) => Err(i + 1),
+            None => Ok(()),
+        }
+    }
+
+    /// Builds a double-array trie with key-value pairs yielded by `iter` and returns it when build
+    /// finished successfully. Otherwise, returns `None`.
+    /// The items must be sorted by the byte order of keys, and this precondition is not checked.
+    /// Currently the items are buffered internally before building.
+    pub fn build_from_sorted_iter<I, T>(iter: I) -> Option<Vec<u8>>
+    where
+        I: IntoIterator<Item = (T, u32)>,
+        T: AsRef<[u8]>,
+    {
+        let keyset = iter.into_iter().collect::<Vec<_>>();
+        Self::build(&keyset)
+    }
+
+    /// Builds a double-array trie with key-value pairs yielded by `iter` in any order and returns
+    /// it when build finished successfully. Otherwise, returns `None`.
+    /// The items are sorted by the byte order of keys internally, and if a key is yielded more than
+    /// once, the last value is kept like inserting into a map.
+    ///
+    /// ```
+    /// use yada::builder::DoubleArrayBuilder;
+    /// use yada::DoubleArray;
+    ///
+    /// let keyset = vec![("b", 1), ("a", 0), ("b", 2)];
+    /// let da = DoubleArray::new(DoubleArrayBuilder::build_from_iter(keyset).unwrap());
+    /// assert_eq!(da.exact_match_search("a"), Some(0));
+    /// assert_eq!(da.exact_match_search("b"), Some(2));
+    /// ```
+    pub fn build_from_iter<I, T>(iter: I) -> Option<Vec<u8>>
+    where
+        I: IntoIterator<Item = (T, u32)>,
+        T: AsRef<[u8]>,
+    {
+        let mut keyset = iter.into_iter().collect::<Vec<_>>();
+        // the sort is stable, so the last one of duplicated keys is the last yielded one
+        keyset.sort_by(|a, b| a.0.as_ref().cmp(b.0.as_ref()));
+        dedup_keep_last(&mut keyset);
+        Self::build(&keyset)
+    }
+
+    /// Builds a double-array trie with key-value pairs in a `map` and returns it when build
+    /// finished successfully. Otherwise, returns `None`.
+    /// The trie relies on the byte order of keys, so the entries are sorted again only when the
+    /// order of `K` doesn't agree with it.
+    pub fn build_from_btreemap<K>(map: &BTreeMap<K, u32>) -> Option<Vec<u8>>
+    where
+        K: AsRef<[u8]> + Ord,
+    {
+        let keyset = map
+            .iter()
+            .map(|(key, value)| (key.as_ref(), *value))
+            .collect::<Vec<_>>();
+        Self::build_from_unsorted(keyset)
+    }
+
+    /// Builds a double-array trie with key-value pairs in a `map` and returns it when build
+    /// finished successfully. Otherwise, returns `None`.
+    /// The entries are sorted by the byte order of keys internally, and `None` is returned if
+    /// different keys have the same bytes.
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use yada::builder::DoubleArrayBuilder;
+    /// use yada::DoubleArray;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert("ab", 1);
+    /// map.insert("a", 0);
+    /// map.insert("b", 2);
+    ///
+    /// let da = DoubleArray::new(DoubleArrayBuilder::build_from_hashmap(&map).unwrap());
+    /// assert_eq!(da.exact_match_search("ab"), Some(1));
+    /// assert_eq!(da.exact_match_search("c"), None);
+    /// ```
+    pub fn build_from_hashmap<K, S>(map: &HashMap<K, u32, S>) -> Option<Vec<u8>>
+    where
+        K: AsRef<[u8]>,
+    {
+        let keyset = map
+            .iter()
+            .map(|(key, value)| (key.as_ref(), *value))
+            .collect::<Vec<_>>();
+        Self::build_from_unsorted(keyset)
+    }
+
+    fn build_from_unsorted(mut keyset: Vec<(&[u8], u32)>) -> Option<Vec<u8>> {
+        if !keyset.windows(2).all(|pair| pair[0].0 <= pair[1].0) {
+            keyset.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        }
+        if keyset.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return None; // duplicated keys
+        }
+        Self::build(&keyset)
+    }
+
+    /// Builds a double-array trie with a `keyset` and returns it when build finished successfully.
+    /// Otherwise, returns `None`.
+    /// The `keyset` must be sorted.
+    pub fn build_from_keyset<T>(&mut self, keyset: &[(T, u32)]) -> Option<Vec<u8>>
+    where
+        T: AsRef<[u8]>,
+    {
+        self.try_build_from_keyset(keyset).ok()
+    }
+
+    /// Builds a double-array trie with a `keyset` and returns it when build finished successfully.
+    /// Otherwise, returns a `BuildError` that describes why the build failed.
+    /// The `keyset` must be sorted.
+    pub fn try_build_from_keyset<T>(&mut self, keyset: &[(T, u32)]) -> Result<Vec<u8>, BuildError>
+    where
+        T: AsRef<[u8]>,
+    {
+        self.build_from_keyset_with_progress(keyset, &mut |_, _| {})
+    }
+
+    /// Builds a double-array trie with a `keyset` like `build()`, and calls `callback` with the
+    /// number of processed keys and the number of all keys whenever the build advances to a range
+    /// of keys that have a different first byte.
+    /// The `keyset` must be sorted.
+    pub fn build_with_progress<T, F>(keyset: &[(T, u32)], mut callback: F) -> Option<Vec<u8>>
+    where
+        T: AsRef<[u8]>,
+        F: FnMut(usize, usize),
+    {
+        Self::new()
+            .build_from_keyset_with_progress(keyset, &mut callback)
+            .ok()
+    }
+
+    fn build_from_keyset_with_progress<T>(
+        &mut self,
+        keyset: &[(T, u32)],
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> Result<Vec<u8>, BuildError>
+    where
+        T: AsRef<[u8]>,
+    {
+        self.build_blocks(keyset, progress)?;
+
+        if self.wide_fields.is_some() {
+            return Ok(self.serialize_wide());
+        }
+        Ok(self.serialize())
+    }
+
+    /// Builds blocks of a double-array trie with a `keyset` without serializing them.
+    fn build_blocks<T>(
+        &mut self,
+        keyset: &[(T, u32)],
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> Result<(), BuildError>
+    where
+        T: AsRef<[u8]>,
+    {
+        // values must be represented as 31 bits integer except for the wide format
+        let max_value = if self.wide_fields.is_some() {
+            u32::MAX
+        } else {
+            (1 << 31) - 1
+        };
+        if let Some(index) = keyset.iter().position(|(_, value)| *value > max_value) {
+            let value = keyset[index].1;
+            return Err(BuildError::ValueTooLarge {
+                index: Some(index),
+                value,
+            });
+        }
+
+        self.reserve(0)?; // reserve root node
+        self.build_nodes(keyset, progress)?;
+        self.shrink();
+        progress(keyset.len(), keyset.len());
+
+        Ok(())
+    }
+
+    /// Builds a double-array trie with a TSV read from `r`, whose lines are keys and values
+    /// separated by a tab (`key\tvalue`), and returns it when build finished successfully.
+    /// Otherwise, returns a `BuildError` with the line number if a line is malformed.
+    /// Empty lines are skipped, and the keys must be sorted.
+    pub fn build_from_tsv_reader<R>(mut r: R) -> Result<Vec<u8>, BuildError>
+    where
+        R: BufRead,
+    {
+        let mut keyset: Vec<(Vec<u8>, u32)> = vec![];
+        let mut buf = vec![];
+        for line in 1.. {
+            buf.clear();
+            if r.read_until(b'\n', &mut buf)
+                .map_err(|e| BuildError::Io(e.kind()))?
+                == 0
+            {
+                break;
+            }
+            let mut bytes = buf.as_slice();
+            if bytes.ends_with(b"\n") {
+                bytes = &bytes[..bytes.len() - 1];
+            }
+            if bytes.ends_with(b"\r") {
+                bytes = &bytes[..bytes.len() - 1];
+            }
+            if bytes.is_empty() {
+                continue;
+            }
+
+            let tab = bytes
+                .iter()
+                .position(|&b| b == b'\t')
+                .ok_or(BuildError::InvalidLine { line })?;
+            let (key, value) = (&bytes[..tab], &bytes[tab + 1..]);
+            let value = std::str::from_utf8(value)
+                .ok()
+                .and_then(|value| value.parse::<u32>().ok())
+                .ok_or(BuildError::InvalidLine { line })?;
+            if let Some((prev, _)) = keyset.last() {
+                if prev.as_slice() >= key {
+                    let index = keyset.len();
+                    return Err(if prev.as_slice() == key {
+                        BuildError::DuplicateKey {
+                            index: Some(index),
+                            position: key.len(),
+                        }
+                    } else {
+                        BuildError::UnsortedKeys { index }
+                    });
+                }
+            }
+            keyset.push((key.to_vec(), value));
+        }
+        Self::try_build(&keyset)
+    }
+
+    /// Builds a double-array trie with a `keyset` like `build()`, and writes it to `w` block by
+    /// block instead of serializing it into a `Vec`, so that the blocks and the serialized bytes
+    /// are not held in memory at once. A build failure is returned as an error of
+    /// `io::ErrorKind::InvalidInput`.
+    /// The `keyset` must be sorted.
+    pub fn write_built<T, W>(keyset: &[(T, u32)], w: &mut W) -> io::Result<()>
+    where
+        T: AsRef<[u8]>,
+        W: Write,
+    {
+        let mut builder = Self::new();
+        builder
+            .build_blocks(keyset, &mut |_, _| {})
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        let mut bytes = [0u8; BLOCK_SIZE * UNIT_SIZE];
+        for block in builder.blocks.drain(..) {
+            for (unit, chunk) in block.units.iter().zip(bytes.chunks_mut(UNIT_SIZE)) {
+                chunk.copy_from_slice(&unit.as_u32().to_le_bytes());
+            }
+            w.write_all(&bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Inserts a `key` and its associated `value` into the double-array trie under construction.
+    /// Keys can be inserted in any order. Conflicting children are relocated when the offset of a
+    /// node collides with other nodes.
+    /// Call `freeze()` after all keys are inserted. This method must not be mixed with
+    /// `build_from_keyset()`. The index of the key in an error is `None`.
+    pub fn insert<K>(&mut self, key: K, value: u32) -> Result<(), BuildError>
+    where
+        K: AsRef<[u8]>,
+    {
+        let key = key.as_ref();
+        if let Some(position) = key.iter().position(|&b| b == 0) {
+            return Err(BuildError::NullCharacter {
+                index: None,
+                position,
+            });
+        }
+        if value >= (1 << 31) {
+            return Err(BuildError::ValueTooLarge { index: None, value });
+        }
+
+        if !self.is_used(0) {
+            self.reserve(0)?; // reserve root node
+        }
+
+        // nodes and offsets added by this insertion, which are removed again if it fails
+        let mut new_units = vec![];
+        let mut new_offsets = vec![];
+        let result = self.insert_nodes(key, value, &mut new_units, &mut new_offsets);
+        if result.is_err() {
+            for (unit_id, offset) in new_offsets {
+                self.used_offsets.remove(&offset);
+                self.get_unit_mut(unit_id).set_offset(0);
+            }
+            for unit_id in new_units.into_iter().rev() {
+                *self.get_unit_mut(unit_id) = Unit::default();
+                self.release(unit_id);
+            }
+        }
+        result
+    }
+
+    /// Adds nodes of a `key` for `insert()`, and records the new nodes and the offsets set to
+    /// nodes without children in `new_units` and `new_offsets`. Relocations keep the trie valid,
+    /// so they are not recorded.
+    fn insert_nodes(
+        &mut self,
+        key: &[u8],
+        value: u32,
+        new_units: &mut Vec<UnitID>,
+        new_offsets: &mut Vec<(UnitID, u32)>,
+    ) -> Result<(), BuildError> {
+        // the root node has children iff any offset has been used
+        let mut has_children = !self.used_offsets.is_empty();
+        let mut node_id: UnitID = 0;
+
+        // traverse labels followed by NULL ('\0')
+        for label in key.iter().copied().chain(std::iter::once(0)) {
+            let child_id = if has_children {
+                let unit = self.get_unit_mut(node_id);
+                let offset = unit.offset() ^ node_id as u32;
+                let child_id = (offset ^ label as u32) as UnitID;
+                if self.has_child(node_id, offset, label) {
+                    if label == 0 {
+                        return Err(BuildError::DuplicateKey {
+                            index: None,
+                            position: key.len(),
+                        });
+                    }
+                    node_id = child_id;
+                    continue;
+                }
+                if self.is_used(child_id) {
+                    self.relocate(node_id, label)?
+                } else {
+                    child_id
+                }
+            } else {
+                let offset = self.find_offset_or_extend(node_id, &[label], |_| true)?;
+                self.set_offset(node_id, offset)?;
+                new_offsets.push((node_id, offset));
+                (offset ^ label as u32) as UnitID
+            };
+
+            // populate label or associated value to the new child node
+            self.reserve(child_id)?;
+            new_units.push(child_id);
+            if label == 0 {
+                self.get_unit_mut(node_id).set_has_leaf(true);
+                self.get_unit_mut(child_id).set_value(value);
+            } else {
+                self.get_unit_mut(child_id).set_label(label);
+            }
+
+            node_id = child_id;
+            has_children = false;
+        }
+
+        Ok(())
+    }
+
+    /// Constructs a new `DoubleArrayBuilder` which contains all nodes of a serialized double-array
+    /// trie, so that more keys can be added by `insert()` or `append_sorted()` without building
+    /// the existing keys again. Returns an error if the bytes are not a valid double array.
+    pub fn from_existing(bytes: &[u8]) -> Result<Self, DoubleArrayError> {
+        let da = DoubleArray::from_bytes(bytes)?;
+        da.validate().map_err(DoubleArrayError::InvalidStructure)?;
+
+        // copy units reachable from the root, and restore used units and offsets
+        let mut builder = Self::new();
+        let mut stack = vec![0 as UnitID];
+        while let Some(unit_id) = stack.pop() {
+            let mut unit = da.unit_at(unit_id).unwrap();
+            if unit_id == 0 {
+                unit.set_label(0); // the label of the root may be a flag of the headered format
+            }
+            builder
+                .reserve(unit_id)
+                .expect("offsets of a valid double array are within the block limit");
+            *builder.get_unit_mut(unit_id) = unit;
+            if unit.is_leaf() {
+                continue;
+            }
+            builder.used_offsets.insert(unit.offset() ^ unit_id as u32);
+            stack.extend(da.children(unit_id).map(|(_, child_pos)| child_pos));
+        }
+        Ok(builder)
+    }
+
+    /// Inserts keys and values of a sorted `keyset` by `insert()`. The keys don't have to be
+    /// greater than keys already in the trie. Call `freeze()` after all keys are inserted.
+    pub fn append_sorted<T>(&mut self, keyset: &[(T, u32)]) -> Result<(), BuildError>
+    where
+        T: AsRef<[u8]>,
+    {
+        Self::check_sorted(keyset).map_err(|index| BuildError::UnsortedKeys { index })?;
+        for (index, (key, value)) in keyset.iter().enumerate() {
+            self.insert(key, *value)
+                .map_err(|err| err.with_index(index))?;
+        }
+        Ok(())
+    }
+
+    /// Returns a double-array trie built by `insert()`.
+    pub fn freeze(mut self) -> Vec<u8> {
+        self.shrink();
+        self.serialize()
+    }
+
+    /// Drops trailing blocks which have no used units, and returns the number of bytes saved.
+    /// Search results don't change because no node has an offset that points into unused blocks.
+    pub fn shrink(&mut self) -> usize {
+        let num_blocks = self.blocks.len();
+        while self.blocks.len() > 1 && self.blocks.last().unwrap().num_unused == BLOCK_SIZE {
+            self.blocks.pop();
+        }
+        let num_shrunk_bytes = (num_blocks - self.blocks.len()) * BLOCK_SIZE * UNIT_SIZE;
+        self.num_shrunk_bytes += num_shrunk_bytes;
+        num_shrunk_bytes
+    }
+
+    fn units(&self) -> impl Iterator<Item = Unit> + '_ {
+        self.blocks
+            .iter()
+            .flat_map(|block| block.units.iter().copied())
+    }
+
+    fn serialize(&self) -> Vec<u8> {
+        let mut da_bytes = Vec::with_capacity(self.blocks.len() * BLOCK_SIZE * UNIT_SIZE);
+        for unit in self.units() {
+            da_bytes.extend_from_slice(&unit.as_u32().to_le_bytes());
+        }
+        da_bytes
+    }
+
+    fn serialize_wide(&self) -> Vec<u8> {
+        let fields = self.wide_fields.as_ref().unwrap();
+        let mut da_bytes =
+            Vec::with_capacity(WIDE_HEADER.len() + self.blocks.len() * BLOCK_SIZE * WIDE_UNIT_SIZE);
+        da_bytes.extend_from_slice(&WIDE_HEADER);
+        for block in &self.blocks {
+            for (i, unit) in block.units.iter().enumerate() {
+                let field = fields.get(block.id * BLOCK_SIZE + i).copied().unwrap_or(0);
+                let mut wide_unit = Unit64::new();
+                if unit.is_leaf() {
+                    wide_unit.set_value(field);
+                } else {
+                    wide_unit.set_label(unit.label() as u8);
+                    wide_unit.set_has_leaf(unit.has_leaf());
+                    wide_unit.set_offset(field as u64);
+                }
+                da_bytes.extend_from_slice(&wide_unit.as_u64().to_le_bytes());
+            }
+        }
+        debug_assert_eq!(
+            da_bytes.len(),
+            WIDE_HEADER.len() + self.blocks.len() * BLOCK_SIZE * WIDE_UNIT_SIZE
+        );
+        da_bytes
+    }
+
+    /// Resets this builder to the initial state with keeping allocated memory and settings, so
+    /// that it can be reused to build another double-array trie.
+    pub fn reset(&mut self) {
+        self.blocks.truncate(1);
+        self.blocks[0] = DoubleArrayBlock::new(0);
+        self.used_offsets.clear();
+        self.num_shrunk_bytes = 0;
+        if let Some(fields) = self.wide_fields.as_mut() {
+            fields.clear();
+        }
+    }
+
+    /// Returns the number of `Unit`s that this builder contains.
+    pub fn num_units(&self) -> u32 {
+        (self.blocks.len() * BLOCK_SIZE) as u32
+    }
+
+    /// Returns the number of `DoubleArrayBlock`s that this builder contains.
+    pub fn num_blocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Returns the ratio of used units to `BLOCK_SIZE` for each block.
+    pub fn block_fill_rates(&self) -> Vec<f64> {
+        self.blocks
+            .iter()
+            .map(|block| (BLOCK_SIZE - block.num_unused) as f64 / BLOCK_SIZE as f64)
+            .collect()
+    }
+
+    /// Returns the number of used `Unit`s that this builder contains.
+    pub fn num_used_units(&self) -> u32 {
+        self.blocks
+            .iter()
+            .map(|block| {
+                block
+                    .used_bits
+                    .iter()
+                    .map(|bits| bits.count_ones())
+                    .sum::<u32>()
+            })
+            .sum::<u32>()
+    }
+
+    /// Returns an estimated size in bytes of memory allocated by this builder for blocks, used
+    /// offsets and fields of the wide format.
+    pub fn memory_size(&self) -> usize {
+        let blocks_size = self.blocks.capacity() * std::mem::size_of::<DoubleArrayBlock>();
+        // a hash set stores a control byte for each entry in addition to the entry itself
+        let used_offsets_size = self.used_offsets.capacity() * (std::mem::size_of::<u32>() + 1);
+        let wide_fields_size = self
+            .wide_fields
+            .as_ref()
+            .map_or(0, |fields| fields.capacity() * std::mem::size_of::<u32>());
+        std::mem::size_of::<Self>() + blocks_size + used_offsets_size + wide_fields_size
+    }
+
+    /// Returns how many times each label appears in used units of internal nodes except the root,
+    /// that is, the number of edges labeled with each byte. Leaves are not counted because they
+    /// store values instead of labels.
+    pub fn label_histogram(&self) -> [u64; 256] {
+        let mut histogram = [0u64; 256];
+        for block in &self.blocks {
+            for (i, unit) in block.units.iter().enumerate() {
+                let unit_id = block.id * BLOCK_SIZE + i;
+                if unit_id == 0 || !block.is_used(i as u8) || unit.is_leaf() {
+                    continue;
+                }
+                histogram[unit.label() as usize] += 1;
+            }
+        }
+        histogram
+    }
+
+    /// Returns statistics of the double-array trie that this builder contains.
+    pub fn stats(&self) -> BuildStats {
+        let num_units = self.num_units();
+        let num_used_units = self.num_used_units();
+        BuildStats {
+            num_units,
+            num_used_units,
+            num_blocks: self.num_blocks(),
+            fill_rate: num_used_units as f64 / num_units as f64,
+            num_shrunk_bytes: self.num_shrunk_bytes,
+        }
+    }
+
+    fn get_block(&self, unit_id: UnitID) -> Option<&DoubleArrayBlock> {
+        self.blocks.get(unit_id / BLOCK_SIZE)
+    }
+
+    fn get_block_mut(&mut self, unit_id: UnitID) -> Option<&mut DoubleArrayBlock> {
+        self.blocks.get_mut(unit_id / BLOCK_SIZE)
+    }
+
+    fn extend_block(&mut self) -> Result<&DoubleArrayBlock, BuildError> {
+        let block_id = self.blocks.len();
+        if block_id >= self.max_blocks {
+            return Err(BuildError::BlockLimitExceeded);
+        }
+        if block_id >= MAX_BLOCKS {
+            return Err(BuildError::OffsetOverflow);
+        }
+        self.blocks.push(DoubleArrayBlock::new(block_id));
+        Ok(self.blocks.last().unwrap())
+    }
+
+    fn get_unit_mut(&mut self, unit_id: UnitID) -> &mut Unit {
+        let block = self.get_block_mut(unit_id).unwrap();
+        &mut block.units[unit_id % BLOCK_SIZE]
+    }
+
+    fn reserve(&mut self, unit_id: UnitID) -> Result<(), BuildError> {
+        while self.get_block(unit_id).is_none() {
+            self.extend_block()?;
+        }
+        let block = self.get_block_mut(unit_id).unwrap();
+        block.reserve((unit_id % BLOCK_SIZE) as u8);
+        Ok(())
+    }
+
+    fn is_used(&self, unit_id: UnitID) -> bool {
+        match self.get_block(unit_id) {
+            Some(block) => block.is_used((unit_id % BLOCK_SIZE) as u8),
+            None => false,
+        }
+    }
+
+    fn release(&mut self, unit_id: UnitID) {
+        let block = self.get_block_mut(unit_id).unwrap();
+        block.release((unit_id % BLOCK_SIZE) as u8);
+    }
+
+    /// Returns true if the node at `unit_id` whose absolute offset is `offset` has a child labeled
+    /// `label`.
+    fn has_child(&mut self, unit_id: UnitID, offset: u32, label: u8) -> bool {
+        if label == 0 {
+            return self.get_unit_mut(unit_id).has_leaf();
+        }
+        // offsets are unique, so a used unit with the same label must be a child of this node
+        let child_id = (offset ^ label as u32) as UnitID;
+        self.is_used(child_id) && self.get_unit_mut(child_id).label() == label as u32
+    }
+
+    /// Sets the absolute `offset` to the node at `unit_id` and marks the offset used.
+    fn set_offset(&mut self, unit_id: UnitID, offset: u32) -> Result<(), BuildError> {
+        if self.wide_fields.is_some() {
+            self.used_offsets.insert(offset);
+            self.set_wide_field(unit_id, offset ^ unit_id as u32);
+            return Ok(());
+        }
+        if offset >= offset_limit() {
+            return Err(BuildError::OffsetOverflow);
+        }
+        self.used_offsets.insert(offset);
+        self.get_unit_mut(unit_id)
+            .set_offset(offset ^ unit_id as u32);
+        Ok(())
+    }
+
+    /// Returns the relative offset of the node at `unit_id`.
+    fn relative_offset(&mut self, unit_id: UnitID) -> u32 {
+        match self.wide_fields.as_ref() {
+            Some(fields) => fields.get(unit_id).copied().unwrap_or(0),
+            None => self.get_unit_mut(unit_id).offset(),
+        }
+    }
+
+    fn set_wide_field(&mut self, unit_id: UnitID, field: u32) {
+        let fields = self.wide_fields.as_mut().unwrap();
+        if fields.len() <= unit_id {
+            fields.resize(unit_id + 1, 0);
+        }
+        fields[unit_id] = field;
+    }
+
+    /// Moves all children of the node at `unit_id` to a new offset where a new child labeled
+    /// `label` also fits, and returns the position of the new child.
+    fn relocate(&mut self, unit_id: UnitID, label: u8) -> Result<UnitID, BuildError> {
+        let old_offset = self.get_unit_mut(unit_id).offset() ^ unit_id as u32;
+        let mut labels = (0..=255u8)
+            .filter(|&l| self.has_child(unit_id, old_offset, l))
+            .collect::<Vec<_>>();
+        labels.push(label);
+        labels.sort_unstable();
+
+        // offsets of grandchildren are relative, so moved children must still be able to encode them
+        // (children except leaves always have their own children)
+        let grandchild_offsets = labels
+            .iter()
+            .filter(|&&l| l != 0 && l != label)
+            .map(|&l| {
+                let child_id = (old_offset ^ l as u32) as UnitID;
+                (l, self.get_unit_mut(child_id).offset() ^ child_id as u32)
+            })
+            .collect::<Vec<_>>();
+        let new_offset = self.find_offset_or_extend(unit_id, &labels, |offset| {
+            grandchild_offsets.iter().all(|&(l, grandchild_offset)| {
+                is_valid_relative_offset(grandchild_offset ^ offset ^ l as u32)
+            })
+        })?;
+        if new_offset >= offset_limit() {
+            return Err(BuildError::OffsetOverflow);
+        }
+
+        for &l in labels.iter().filter(|&&l| l != label) {
+            let old_id = (old_offset ^ l as u32) as UnitID;
+            let new_id = (new_offset ^ l as u32) as UnitID;
+            let mut unit = *self.get_unit_mut(old_id);
+            if let Some(&(_, grandchild_offset)) =
+                grandchild_offsets.iter().find(|&&(gl, _)| gl == l)
+            {
+                unit.set_offset(grandchild_offset ^ new_id as u32);
+            }
+            self.reserve(new_id)?;
+            *self.get_unit_mut(new_id) = unit;
+            self.release(old_id);
+        }
+
+        self.used_offsets.remove(&old_offset);
+        self.set_offset(unit_id, new_offset)?;
+
+        Ok((new_offset ^ label as u32) as UnitID)
+    }
+
+    fn find_offset_or_extend<F>(
+        &mut self,
+        unit_id: UnitID,
+        labels: &[u8],
+        is_valid: F,
+    ) -> Result<u32, BuildError>
+    where
+        F: Fn(u32) -> bool,
+    {
+        let labels = labels.to_vec();
+        loop {
+            if let Some(offset) = self.find_offset_by(unit_id, &labels, &is_valid) {
+                break Ok(offset);
+            }
+            self.extend_block()?;
+        }
+    }
+
+    /// Builds nodes of a `keyset` in depth-first order with an explicit stack instead of
+    /// recursion, so that long keys don't overflow the call stack.
+    fn build_nodes<T>(
+        &mut self,
+        keyset: &[(T, u32)],
+        progress: &mut dyn FnMut(usize, usize),
+    ) -> Result<(), BuildError>
+    where
+        T: AsRef<[u8]>,
+    {
+        // element of stack is a tuple (depth, begin, end, unit_id)
+        let mut stack = vec![(0, 0, keyset.len(), 0 as UnitID)];
+        // scratch buffers reused for each node
+        let mut labels = Vec::with_capacity(256);
+        let mut label_bytes = Vec::with_capacity(256);
+        while let Some((depth, begin, end, unit_id)) = stack.pop() {
+            if depth == 1 {
+                progress(begin, keyset.len());
+            }
+            self.build_node(
+                keyset,
+                (depth, begin, end, unit_id),
+                &mut stack,
+                &mut labels,
+                &mut label_bytes,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Builds children of the node at `unit_id` for keys in `begin..end`, and pushes the children
+    /// except leaves to `stack`. `labels` and `label_bytes` are scratch buffers.
+    fn build_node<T>(
+        &mut self,
+        keyset: &[(T, u32)],
+        (depth, begin, end, unit_id): (usize, usize, usize, UnitID),
+        stack: &mut Vec<(usize, usize, usize, UnitID)>,
+        labels: &mut Vec<(u8, usize, usize)>,
+        label_bytes: &mut Vec<u8>,
+    ) -> Result<(), BuildError>
+    where
+        T: AsRef<[u8]>,
+    {
+        // element of labels is a tuple (label, start_position, end_position)
+        labels.clear();
+        let mut value = None;
+
+        for i in begin..end {
+            let key_value = keyset.get(i).unwrap();
+            let label = match key_value.0.as_ref().get(depth) {
+                Some(0) => {
+                    return Err(BuildError::NullCharacter {
+                        index: Some(i),
+                        position: depth,
+                    })
+                }
+                Some(&label) => label,
+                None => 0,
+            };
+            if label == 0 {
+                match value {
+                    Some(value) if self.dedup && value == key_value.1 => {} // collapsed
+                    Some(_) => {
+                        // there is just one '\0' in a key
+                        return Err(BuildError::DuplicateKey {
+                            index: Some(i),
+                            position: depth,
+                        });
+                    }
+                    None => value = Some(key_value.1),
+                }
+            }
+            match labels.last_mut() {
+                Some(last_label) => {
+                    if last_label.0 > label {
+                        return Err(BuildError::UnsortedKeys { index: i });
+                    }
+                    if last_label.0 != label {
+                        last_label.2 = i; // set end position
+                        labels.push((label, i, 0));
+                    }
+                }
+                None => {
+                    labels.push((label, i, 0));
+                }
+            }
+        }
+        assert!(!labels.is_empty());
+
+        let last_label = labels.last_mut().unwrap();
+        last_label.2 = end;
+
+        label_bytes.clear();
+        label_bytes.extend(labels.iter().map(|(key, _, _)| *key));
+
+        // search an offset where these children fits to unused positions.
+        let offset: u32 = loop {
+            if let Some(offset) = self.find_offset(unit_id, label_bytes) {
+                break offset;
+            }
+            self.extend_block()?;
+        };
+
+        let has_leaf = label_bytes.first().filter(|&&x| x == 0).is_some();
+
+        // populate offset and has_leaf flag to parent node
+        if self.relative_offset(unit_id) != 0 || self.get_unit_mut(unit_id).has_leaf() {
+            // the parent node has been already built
+            return Err(BuildError::UnsortedKeys { index: begin });
+        }
+        self.get_unit_mut(unit_id).set_has_leaf(has_leaf);
+        self.set_offset(unit_id, offset)?; // mark the offset used
+
+        // populate label or associated value to children node
+        let wide = self.wide_fields.is_some();
+        for &label in label_bytes.iter() {
+            let child_id = (offset ^ label as u32) as UnitID;
+            self.reserve(child_id)?;
+
+            let unit = self.get_unit_mut(child_id);
+
+            // child node units should be empty
+            if unit.as_u32() != 0 {
+                return Err(BuildError::UnsortedKeys { index: begin });
+            }
+
+            if label == 0 {
+                let value = value.unwrap();
+                if wide {
+                    // the value of the wide format is stored on serialization
+                    unit.set_value(0);
+                    self.set_wide_field(child_id, value);
+                } else {
+                    unit.set_value(value);
+                }
+            } else {
+                unit.set_label(label);
+            }
+        }
+
+        // push children in reverse order to build them in depth-first order (leaves have no
+        // children)
+        for &(label, begin, end) in labels.iter().rev().filter(|&&(label, _, _)| label != 0) {
+            stack.push((depth + 1, begin, end, (label as u32 ^ offset) as UnitID));
+        }
+
+        Ok(())
+    }
+
+    fn find_offset(&self, unit_id: UnitID, labels: &Vec<u8>) -> Option<u32> {
+        self.find_offset_by(unit_id, labels, |_| true)
+    }
+
+    fn find_offset_by<F>(&self, unit_id: UnitID, labels: &Vec<u8>, is_valid: F) -> Option<u32>
+    where
+        F: Fn(u32) -> bool,
+    {
+        let head_block = self.blocks.len().saturating_sub(self.num_target_blocks);
+        self.blocks
+            .iter()
+            .skip(head_block) // search for offset in last N blocks
+            .filter(|block| block.num_unused >= labels.len()) // all labels must fit in a block
+            .find_map(|block| {
+                // find the first valid offset in a block
+                for offset in block.find_offset(unit_id, labels, self.wide_fields.is_some()) {
+                    let offset_u32 = (block.id as u32) << BLOCK_BITS | offset as u32;
+                    if !self.used_offsets.contains(&offset_u32) && is_valid(offset_u32) {
+                        return Some((block.id as u32) << BLOCK_BITS | offset as u32);
+                    }
+                }
+                None
+            })
+    }
+}
+
+const DEFAULT_UNITS: [Unit; BLOCK_SIZE] = [Unit::new(); BLOCK_SIZE];
+const DEFAULT_USED_BITS: [u64; BLOCK_SIZE / 64] = [0; BLOCK_SIZE / 64];
+const DEFAULT_NEXT_UNUSED: [u8; BLOCK_SIZE] = {
+    let mut next_unused = [INVALID_NEXT; BLOCK_SIZE];
+    let mut i = 0;
+    while i < next_unused.len() - 1 {
+        next_unused[i] = (i + 1) as u8;
+        i += 1;
+    }
+    next_unused
+};
+const DEFAULT_PREV_UNUSED: [u8; BLOCK_SIZE] = {
+    let mut prev_unused = [INVALID_PREV; BLOCK_SIZE];
+    let mut i = 1;
+    while i < prev_unused.len() {
+        prev_unused[i] = (i - 1) as u8;
+        i += 1;
+    }
+    prev_unused
+};
+
+/// A block that have a shard of a double-array and other useful data structures.
+pub struct DoubleArrayBlock {
+    pub id: usize,
+    pub units: [Unit; BLOCK_SIZE],
+    pub used_bits: [u64; BLOCK_SIZE / 64], // bitset of used units
+    pub head_unused: u8,
+    pub next_unused: [u8; BLOCK_SIZE],
+    pub prev_unused: [u8; BLOCK_SIZE],
+    pub num_unused: usize,
+}
+
+impl DoubleArrayBlock {
+    const fn new(id: usize) -> Self {
+        Self {
+            id,
+            units: DEFAULT_UNITS,
+            used_bits: DEFAULT_USED_BITS,
+            head_unused: 0,
+            next_unused: DEFAULT_NEXT_UNUSED,
+            prev_unused: DEFAULT_PREV_UNUSED,
+            num_unused: BLOCK_SIZE,
+        }
+    }
+
+    /// Finds a valid offset in this block.
+    fn find_offset<'a>(
+        &'a self,
+        unit_id: UnitID,
+        labels: &'a Vec<u8>,
+        wide: bool,
+    ) -> impl Iterator<Item = u8> + 'a {
+        assert!(!labels.is_empty());
+        let label_bits = if labels.len() > MIN_DENSE_LABELS {
+            Some(to_bits(labels.iter().skip(1)))
+        } else {
+            None
+        };
+        FindOffset {
+            unused_id: Some(self.head_unused),
+            block: self,
+            unit_id,
+            labels,
+            label_bits,
+            wide,
+        }
+    }
+
+    /// Returns true if the unit at `id` is used.
+    #[inline]
+    pub fn is_used(&self, id: u8) -> bool {
+        self.used_bits[id as usize / 64] >> (id % 64) & 1 == 1
+    }
+
+    #[inline]
+    fn set_used(&mut self, id: u8, is_used: bool) {
+        if is_used {
+            self.used_bits[id as usize / 64] |= 1 << (id % 64);
+        } else {
+            self.used_bits[id as usize / 64] &= !(1 << (id % 64));
+        }
+    }
+
+    /// Returns true if all units at `offset ^ label` for `labels` are unused.
+    #[inline]
+    fn are_unused<'a, I>(&self, offset: u8, mut labels: I) -> bool
+    where
+        I: Iterator<Item = &'a u8>,
+    {
+        labels.all(|label| !self.is_used(offset ^ label))
+    }
+
+    /// Returns true if all units at `offset ^ label` for labels in `label_bits` are unused like
+    /// `are_unused()`, but checks 64 labels at once by permuting the bitset of labels.
+    #[inline]
+    fn are_unused_bitwise(&self, offset: u8, label_bits: &[u64; BLOCK_SIZE / 64]) -> bool {
+        // XOR of the upper 2 bits permutes words, and XOR of the lower 6 bits permutes bits
+        (0..label_bits.len()).all(|i| {
+            let bits = xor_permute_bits(label_bits[i], offset & 63);
+            bits & self.used_bits[i ^ (offset >> 6) as usize] == 0
+        })
+    }
+
+    fn reserve(&mut self, id: u8) {
+        // maintain used_bits and num_unused
+        if !self.is_used(id) {
+            self.num_unused -= 1;
+        }
+        self.set_used(id, true);
+
+        let prev_id = self.prev_unused[id as usize];
+        let next_id = self.next_unused[id as usize];
+
+        // maintain next_unused
+        if prev_id != INVALID_PREV {
+            self.next_unused[prev_id as usize] = next_id;
+        }
+        self.next_unused[id as usize] = INVALID_NEXT; // this line can be removed
+
+        // maintain prev_unused
+        if next_id != INVALID_NEXT {
+            self.prev_unused[next_id as usize] = prev_id;
+        }
+        self.prev_unused[id as usize] = INVALID_PREV; // this line can be removed
+
+        // maintain head_unused
+        if id == self.head_unused {
+            self.head_unused = next_id;
+        }
+    }
+
+    fn release(&mut self, id: u8) {
+        // maintain used_bits and num_unused
+        if self.is_used(id) {
+            self.num_unused += 1;
+        }
+        self.set_used(id, false);
+        self.units[id as usize] = Unit::new();
+
+        // keep unused units sorted by id
+        let prev_id = (0..id).rev().find(|&i| !self.is_used(i));
+        let next_id = (id..=255).skip(1).find(|&i| !self.is_used(i));
+
+        // maintain next_unused, prev_unused and head_unused
+        match prev_id {
+            Some(prev_id) => {
+                self.next_unused[prev_id as usize] = id;
+                self.prev_unused[id as usize] = prev_id;
+            }
+            None => {
+                self.prev_unused[id as usize] = INVALID_PREV;
+                self.head_unused = id;
+            }
+        }
+        match next_id {
+            Some(next_id) => {
+                self.prev_unused[next_id as usize] = id;
+                self.next_unused[id as usize] = next_id;
+            }
+            None => {
+                self.next_unused[id as usize] = INVALID_NEXT;
+            }
+        }
+    }
+}
+
+/// Returns a bitset of `ids`.
+fn to_bits<'a, I>(ids: I) -> [u64; BLOCK_SIZE / 64]
+where
+    I: Iterator<Item = &'a u8>,
+{
+    let mut bits = [0u64; BLOCK_SIZE / 64];
+    for &id in ids {
+        bits[id as usize / 64] |= 1 << (id % 64);
+    }
+    bits
+}
+
+/// Moves each bit at `i` of `bits` to `i ^ x` (`x` < 64).
+#[inline]
+fn xor_permute_bits(mut bits: u64, x: u8) -> u64 {
+    const MASKS: [u64; 6] = [
+        0x5555_5555_5555_5555,
+        0x3333_3333_3333_3333,
+        0x0F0F_0F0F_0F0F_0F0F,
+        0x00FF_00FF_00FF_00FF,
+        0x0000_FFFF_0000_FFFF,
+        0x0000_0000_FFFF_FFFF,
+    ];
+    for (k, mask) in MASKS.iter().enumerate() {
+        if x >> k & 1 == 1 {
+            // swap adjacent groups of 2^k bits
+            let shift = 1 << k;
+            bits = (bits & mask) << shift | (bits >> shift) & mask;
+        }
+    }
+    bits
+}
+
+/// Returns true if `offset` can be stored as a relative offset of a unit.
+fn is_valid_relative_offset(offset: u32) -> bool {
+    offset < (1u32 << 21) || (offset < (1u32 << 29) && offset & 0xFF == 0)
+}
+
+pub struct FindOffset<'a> {
+    unused_id: Option<u8>, // None means that all unused units have been visited
+    block: &'a DoubleArrayBlock,
+    unit_id: UnitID, // parent node position to set the offset
+    labels: &'a Vec<u8>,
+    label_bits: Option<[u64; BLOCK_SIZE / 64]>, // bitset of labels except the first one if dense
+    wide: bool, // any relative offset can be stored in units of the wide format
+}
+
+impl<'a> FindOffset<'a> {
+    #[inline]
+    fn is_valid_offset(&self, offset: u8) -> bool {
+        let offset_u32 = (self.block.id as u32) << BLOCK_BITS | offset as u32;
+        let relative_offset = self.unit_id as u32 ^ offset_u32;
+        if !self.wide && (relative_offset & (0xFF << 21)) > 0 && (relative_offset & 0xFF) > 0 {
+            return false;
+        }
+
+        match &self.label_bits {
+            Some(label_bits) => self.block.are_unused_bitwise(offset, label_bits),
+            None => self.block.are_unused(offset, self.labels.iter().skip(1)),
+        }
+    }
+}
+
+impl<'a> Iterator for FindOffset<'a> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut unused_id = self.unused_id?;
+
+        // return if this block is full
+        if self.block.head_unused == INVALID_NEXT && self.block.is_used(0) {
+            assert_eq!(self.block.num_unused, 0); // assert full
+            return None;
+        }
+        assert_ne!(self.block.num_unused, 0); // assert not full
+
+        loop {
+            assert!(!self.block.is_used(unused_id));
+
+            let first_label = *self.labels.first()?;
+            let offset = unused_id ^ first_label;
+
+            let is_valid_offset = self.is_valid_offset(offset);
+
+            // update unused_id to next unused node
+            let next_id = self.block.next_unused[unused_id as usize];
+            self.unused_id = Some(next_id).filter(|&id| id != INVALID_NEXT);
+
+            if is_valid_offset {
+                return Some(offset);
+            }
+
+            unused_id = self.unused_id?;
+        }
+    }
+}
+
+impl std::fmt::Debug for DoubleArrayBlock {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("DoubleArrayBlock")
+            .field(
+                "units",
+                &format_args!(
+                    "[{}]",
+                    self.units
+                        .iter()
+                        .map(|u| u.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            )
+            .field(
+                "is_used",
+                &format_args!(
+                    "[{}]",
+                    (0..=255u8)
+                        .map(|id| self.is_used(id).to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            )
+            .field("head_unused", &self.head_unused)
+            .field("num_unused", &self.num_unused)
+            .field(
+                "next_unused",
+                &format_args!(
+                    "[{}]",
+                    self.next_unused
+                        .iter()
+                        .map(|u| u.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            )
+            .field(
+                "prev_unused",
+                &format_args!(
+                    "[{}]",
+                    self.prev_unused
+                        .iter()
+                        .map(|u| u.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::{
+        to_bits, BuildError, DoubleArrayBlock, DoubleArrayBuilder, BLOCK_BITS, BLOCK_SIZE,
+        OFFSET_LIMIT,
+    };
+    use crate::DoubleArray;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_build() {
+        let keyset: &[(&[u8], u32)] = &[
+            ("a".as_bytes(), 0),
+            ("aa".as_bytes(), 0),
+            ("aaa".as_bytes(), 0),
+            ("aaaa".as_bytes(), 0),
+            ("aaaaa".as_bytes(), 0),
+            ("ab".as_bytes(), 0),
+            ("abc".as_bytes(), 0),
+            ("abcd".as_bytes(), 0),
+            ("abcde".as_bytes(), 0),
+            ("abcdef".as_bytes(), 0),
+        ];
+
+        let mut builder = DoubleArrayBuilder::new();
+        let da = builder.build_from_keyset(keyset);
+        assert!(da.is_some());
+
+        assert!(0 < builder.num_units());
+        assert!(0 < builder.num_used_units());
+        assert!(builder.num_used_units() < builder.num_units());
+
+        let stats = builder.stats();
+        assert_eq!(stats.num_units, builder.num_units());
+        assert_eq!(stats.num_used_units, builder.num_used_units());
+        assert_eq!(stats.num_blocks, 1);
+        assert!(0.0 < stats.fill_rate && stats.fill_rate < 1.0);
+
+        assert_eq!(builder.num_blocks(), 1);
+        assert_eq!(builder.block_fill_rates(), vec![stats.fill_rate]);
+
+        let histogram = builder.label_histogram();
+        assert_eq!(histogram[b'a' as usize], 5);
+        assert_eq!(histogram[b'b' as usize], 1);
+        assert_eq!(histogram[b'f' as usize], 1);
+        assert_eq!(histogram.iter().sum::<u64>(), 10);
+    }
+
+    #[test]
+    fn test_build_long_key() {
+        let long_key = vec![b'a'; 50_000];
+        let keyset = &[(&b"a"[..], 0), (&long_key[..], 1), (&b"b"[..], 2)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+        for (key, value) in keyset {
+            assert_eq!(da.exact_match_search(key), Some(*value));
+        }
+        assert_eq!(da.exact_match_search(&long_key[..49_999]), None);
+    }
+
+    #[test]
+    fn test_build_unsorted_keyset() {
+        // unsorted keysets should be rejected without panics
+        let keyset = &[("goo", 1), ("mag", 2), ("gon", 3)];
+        assert_eq!(DoubleArrayBuilder::build(keyset), None);
+
+        let keyset = &[("ab", 1), ("a", 2)];
+        assert_eq!(DoubleArrayBuilder::build(keyset), None);
+
+        let keyset = &[("a", 1), ("a", 2)];
+        assert_eq!(DoubleArrayBuilder::build(keyset), None);
+
+        let keyset = &[("a\0b", 1)];
+        assert_eq!(DoubleArrayBuilder::build(keyset), None);
+    }
+
+    #[test]
+    fn test_try_build() {
+        let keyset = &[("a", 1), ("ab", 2)];
+        assert_eq!(
+            DoubleArrayBuilder::try_build(keyset).ok(),
+            DoubleArrayBuilder::build(keyset)
+        );
+
+        let keyset = &[("ab", 1), ("a", 2)];
+        assert_eq!(
+            DoubleArrayBuilder::try_build(keyset),
+            Err(BuildError::UnsortedKeys { index: 1 })
+        );
+
+        // offsets beyond the limit are reached by a lowered limit instead of a huge keyset
+        let keyset = (0..1000u32)
+            .map(|i| (format!("{:04}", i), i))
+            .collect::<Vec<_>>();
+        OFFSET_LIMIT.with(|limit| limit.set(BLOCK_SIZE as u32 * 2));
+        let result = DoubleArrayBuilder::try_build(&keyset);
+        let mut builder = DoubleArrayBuilder::new();
+        let inserted = keyset
+            .iter()
+            .try_for_each(|(key, value)| builder.insert(key, *value));
+        OFFSET_LIMIT.with(|limit| limit.set(1 << 29));
+        assert_eq!(result, Err(BuildError::OffsetOverflow));
+        assert_eq!(inserted, Err(BuildError::OffsetOverflow));
+        assert!(DoubleArrayBuilder::try_build(&keyset).is_ok());
+    }
+
+    #[test]
+    fn test_build_error_index() {
+        let mut keyset = (0..1000u32)
+            .map(|i| (format!("{:04}", i).into_bytes(), i))
+            .collect::<Vec<_>>();
+
+        let mut planted = keyset.clone();
+        planted[567].0 = b"05\x007".to_vec();
+        assert_eq!(
+            DoubleArrayBuilder::try_build(&planted),
+            Err(BuildError::NullCharacter {
+                index: Some(567),
+                position: 2
+            })
+        );
+
+        let mut planted = keyset.clone();
+        planted[567].0 = planted[566].0.clone();
+        assert_eq!(
+            DoubleArrayBuilder::try_build(&planted),
+            Err(BuildError::DuplicateKey {
+                index: Some(567),
+                position: 4
+            })
+        );
+
+        let mut planted = keyset.clone();
+        planted.swap(566, 567);
+        assert_eq!(
+            DoubleArrayBuilder::try_build(&planted),
+            Err(BuildError::UnsortedKeys { index: 567 })
+        );
+
+        keyset[567].1 = 1 << 31;
+        assert_eq!(
+            DoubleArrayBuilder::try_build(&keyset),
+            Err(BuildError::ValueTooLarge {
+                index: Some(567),
+                value: 1 << 31
+            })
+        );
+    }
+
+    #[test]
+    fn test_build_too_large_value() {
+        let keyset = &[("a", (1 << 31) - 1)];
+        assert!(DoubleArrayBuilder::try_build(keyset).is_ok());
+
+        let keyset = &[("a", 1 << 31)];
+        assert_eq!(
+            DoubleArrayBuilder::try_build(keyset),
+            Err(BuildError::ValueTooLarge {
+                index: Some(0),
+                value: 1 << 31
+            })
+        );
+        assert_eq!(DoubleArrayBuilder::build(keyset), None);
+    }
+
+    #[test]
+    fn test_check_sorted() {
+        assert_eq!(DoubleArrayBuilder::check_sorted::<&str>(&[]), Ok(()));
+        assert_eq!(
+            DoubleArrayBuilder::check_sorted(&[("a", 0), ("ab", 1), ("b", 2)]),
+            Ok(())
+        );
+        assert_eq!(
+            DoubleArrayBuilder::check_sorted(&[("a", 0), ("b", 1), ("ab", 2), ("aa", 3)]),
+            Err(2)
+        );
+        assert_eq!(
+            DoubleArrayBuilder::check_sorted(&[("a", 0), ("b", 1), ("b", 2)]),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_build_str() {
+        let keyset = &[("a", 0), ("ab", 1), ("あ", 2)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build_str(keyset).unwrap());
+        for (key, value) in keyset {
+            assert_eq!(da.exact_match_search(key), Some(*value));
+        }
+    }
+
+    #[test]
+    fn test_build_i32() {
+        let keyset = &[("a", -(1 << 30)), ("b", -1), ("c", 0), ("d", (1 << 30) - 1)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build_i32(keyset).unwrap());
+        for (key, value) in keyset {
+            assert_eq!(da.exact_match_search_i32(key), Some(*value));
+        }
+        assert_eq!(da.exact_match_search_i32("e"), None);
+
+        assert_eq!(DoubleArrayBuilder::build_i32(&[("a", 1 << 30)]), None);
+        assert_eq!(
+            DoubleArrayBuilder::build_i32(&[("a", -(1 << 30) - 1)]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_write_built() {
+        let keyset = &[("a", 0), ("ab", 1), ("b", 2)];
+        let mut bytes = vec![];
+        DoubleArrayBuilder::write_built(keyset, &mut bytes).unwrap();
+        assert_eq!(Some(bytes), DoubleArrayBuilder::build(keyset));
+
+        let err = DoubleArrayBuilder::write_built(&[("b", 0), ("a", 1)], &mut vec![]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_build_from_tsv_reader() {
+        let tsv = "a\t0\nab\t1\r\n\nb\t2";
+        let da =
+            DoubleArray::new(DoubleArrayBuilder::build_from_tsv_reader(tsv.as_bytes()).unwrap());
+        assert_eq!(da.exact_match_search("a"), Some(0));
+        assert_eq!(da.exact_match_search("ab"), Some(1));
+        assert_eq!(da.exact_match_search("b"), Some(2));
 
-        // maintain prev_unused
-        if next_id != INVALID_NEXT {
-            self.prev_unused[next_id as usize] = prev_id;
+        let cases = [
+            ("a\t0\nb\n", BuildError::InvalidLine { line: 2 }),
+            ("a\t0\n\nb\tx\n", BuildError::InvalidLine { line: 3 }),
+            ("a\t-1\n", BuildError::InvalidLine { line: 1 }),
+            ("b\t0\na\t1\n", BuildError::UnsortedKeys { index: 1 }),
+            (
+                "a\t0\na\t1\n",
+                BuildError::DuplicateKey {
+                    index: Some(1),
+                    position: 1,
+                },
+            ),
+            (
+                "a\t2147483648\n",
+                BuildError::ValueTooLarge {
+                    index: Some(0),
+                    value: 1 << 31,
+                },
+            ),
+        ];
+        for (tsv, err) in cases.iter() {
+            assert_eq!(
+                DoubleArrayBuilder::build_from_tsv_reader(tsv.as_bytes()),
+                Err(err.clone())
+            );
         }
-        self.prev_unused[id as usize] = INVALID_PREV; // this line can be removed
+    }
 
-        // maintain head_unused
-        if id == self.head_unused {
-            self.head_unused = next_id;
+    #[test]
+    fn test_build_with_progress() {
+        let keyset = &[
+            ("a".as_bytes(), 0),
+            ("ab".as_bytes(), 1),
+            ("b".as_bytes(), 2),
+            ("c".as_bytes(), 3),
+            ("cd".as_bytes(), 4),
+        ];
+
+        let mut progress = vec![];
+        let da_bytes = DoubleArrayBuilder::build_with_progress(keyset, |processed, total| {
+            progress.push((processed, total))
+        });
+        assert_eq!(da_bytes, DoubleArrayBuilder::build(keyset));
+        assert_eq!(progress, vec![(0, 5), (2, 5), (3, 5), (5, 5)]);
+    }
+
+    #[test]
+    fn test_shrink() {
+        let keyset = &[("a".as_bytes(), 0), ("ab".as_bytes(), 1)];
+
+        let mut builder = DoubleArrayBuilder::new();
+        let da_bytes = builder.build_from_keyset(keyset).unwrap();
+        assert_eq!(builder.shrink(), 0);
+
+        builder.extend_block().unwrap();
+        builder.extend_block().unwrap();
+        assert_eq!(builder.shrink(), 2 * 256 * 4);
+        assert_eq!(builder.stats().num_blocks, 1);
+        assert_eq!(builder.stats().num_shrunk_bytes, 2 * 256 * 4);
+        assert_eq!(builder.serialize(), da_bytes);
+    }
+
+    #[test]
+    fn test_build_across_blocks() {
+        // positions in blocks other than the first one are shifted by BLOCK_BITS
+        let keyset = (0..5000u32)
+            .map(|i| (format!("{:05}", i), i))
+            .collect::<Vec<_>>();
+        let mut builder = DoubleArrayBuilder::new();
+        let da_bytes = builder.build_from_keyset(&keyset).unwrap();
+        assert!(builder.blocks.len() > 1);
+        for (i, block) in builder.blocks.iter().enumerate() {
+            assert_eq!(block.id, i);
+            assert_eq!(block.units.len(), 1 << BLOCK_BITS);
+        }
+        assert!(da_bytes.len() > BLOCK_SIZE * 4);
+
+        let da = DoubleArray::new(da_bytes);
+        for (key, value) in keyset.iter() {
+            assert_eq!(da.exact_match_search(key), Some(*value));
         }
+        assert_eq!(da.entries().count(), keyset.len());
     }
-}
 
-pub struct FindOffset<'a> {
-    unused_id: u8,
-    block: &'a DoubleArrayBlock,
-    unit_id: UnitID, // parent node position to set the offset
-    labels: &'a Vec<u8>,
-}
+    #[test]
+    fn test_build_with_target_blocks() {
+        let keyset = (0..1000u32)
+            .map(|i| (format!("{:04}", i), i))
+            .collect::<Vec<_>>();
 
-impl<'a> FindOffset<'a> {
-    #[inline]
-    fn is_valid_offset(&self, offset: u8) -> bool {
-        let offset_u32 = (self.block.id as u32) << 8 | offset as u32;
-        let relative_offset = self.unit_id as u32 ^ offset_u32;
-        if (relative_offset & (0xFF << 21)) > 0 && (relative_offset & 0xFF) > 0 {
-            return false;
+        for num_target_blocks in [1, 64].iter() {
+            let mut builder = DoubleArrayBuilder::new().with_target_blocks(*num_target_blocks);
+            let da = DoubleArray::new(builder.build_from_keyset(&keyset).unwrap());
+            for (key, value) in keyset.iter() {
+                assert_eq!(da.exact_match_search(key), Some(*value));
+            }
         }
+    }
 
-        self.labels.iter().skip(1).all(|label| {
-            let id = offset ^ label;
-            match self.block.is_used.get(id as UnitID) {
-                Some(is_used) => !*is_used,
-                None => {
-                    // something is going wrong
-                    assert!(false, "DoubleArrayBlock is_used.get({}) was fault", id);
-                    false
+    #[test]
+    fn test_build_with_estimated_keys() {
+        let keyset = &[("a", 0), ("ab", 1), ("b", 2)];
+        let mut builder = DoubleArrayBuilder::new().with_estimated_keys(1_000);
+        assert!(builder.blocks.capacity() >= 1_000 / 64);
+        assert!(builder.memory_size() > DoubleArrayBuilder::new().memory_size());
+        assert_eq!(
+            builder.build_from_keyset(keyset),
+            DoubleArrayBuilder::build(keyset)
+        );
+    }
+
+    #[test]
+    fn test_build_with_max_blocks() {
+        let keyset = (0..1000u32)
+            .map(|i| (format!("{:04}", i), i))
+            .collect::<Vec<_>>();
+
+        let mut builder = DoubleArrayBuilder::new().with_max_blocks(2);
+        assert_eq!(
+            builder.try_build_from_keyset(&keyset),
+            Err(BuildError::BlockLimitExceeded)
+        );
+
+        let mut builder = DoubleArrayBuilder::new().with_max_blocks(2);
+        for (key, value) in keyset.iter().take(10) {
+            assert_eq!(builder.insert(key, *value), Ok(()));
+        }
+        let result = keyset
+            .iter()
+            .skip(10)
+            .try_for_each(|(key, value)| builder.insert(key, *value));
+        assert_eq!(result, Err(BuildError::BlockLimitExceeded));
+
+        let mut builder = DoubleArrayBuilder::new().with_max_blocks(64);
+        assert!(builder.try_build_from_keyset(&keyset).is_ok());
+    }
+
+    #[test]
+    fn test_insert_after_error() {
+        let keyset = (0..1000u32)
+            .map(|i| {
+                (
+                    format!("{:04}-suffix", i.wrapping_mul(2654435761) % 10000),
+                    i,
+                )
+            })
+            .collect::<Vec<_>>();
+        let num_used = |builder: &DoubleArrayBuilder| {
+            builder
+                .blocks
+                .iter()
+                .map(|block| BLOCK_SIZE - block.num_unused)
+                .sum::<usize>()
+        };
+
+        // insertions fail in the middle of keys when a block is full, and leave no nodes
+        let mut builder = DoubleArrayBuilder::new().with_max_blocks(1);
+        let mut inserted = vec![];
+        let mut failed = vec![];
+        for (key, value) in keyset.iter() {
+            let num_used_before = num_used(&builder);
+            match builder.insert(key, *value) {
+                Ok(()) => inserted.push((key, *value)),
+                Err(e) => {
+                    assert_eq!(e, BuildError::BlockLimitExceeded);
+                    assert_eq!(num_used(&builder), num_used_before);
+                    failed.push((key, *value));
                 }
             }
-        })
-    }
-}
+        }
+        assert!(!failed.is_empty());
 
-impl<'a> Iterator for FindOffset<'a> {
-    type Item = u8;
+        // the failed keys can be inserted again
+        builder.max_blocks = 64;
+        for (key, value) in failed {
+            assert_eq!(builder.insert(key, value), Ok(()));
+            inserted.push((key, value));
+        }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.unused_id == INVALID_NEXT && self.block.is_used[self.unused_id as usize] == true {
-            return None;
+        let da = DoubleArray::new(builder.freeze());
+        assert_eq!(da.validate(), Ok(()));
+        assert_eq!(da.entries().count(), keyset.len());
+        for (key, value) in inserted {
+            assert_eq!(da.exact_match_search(key), Some(value));
         }
+    }
 
-        // return if this block is full
-        if self.block.head_unused == INVALID_NEXT && self.block.is_used[0] == true {
-            assert!(self.block.is_used.iter().all(|is_used| *is_used)); // assert full
-            return None;
+    #[test]
+    fn test_builder() {
+        let keyset = (0..1000u32)
+            .map(|i| (format!("{:04}", i), i))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            DoubleArrayBuilder::builder().build(&keyset),
+            DoubleArrayBuilder::build(&keyset)
+        );
+        assert_eq!(
+            DoubleArrayBuilder::builder()
+                .target_blocks(1)
+                .build(&keyset),
+            DoubleArrayBuilder::new()
+                .with_target_blocks(1)
+                .build_from_keyset(&keyset)
+        );
+        assert_eq!(
+            DoubleArrayBuilder::builder()
+                .max_blocks(2)
+                .try_build(&keyset),
+            Err(BuildError::BlockLimitExceeded)
+        );
+
+        let keyset = &[("b", 1), ("a", 0), ("c", 3), ("b", 2)];
+        assert_eq!(
+            DoubleArrayBuilder::builder().try_build(keyset),
+            Err(BuildError::UnsortedKeys { index: 1 })
+        );
+        assert_eq!(
+            DoubleArrayBuilder::builder()
+                .sort_input(true)
+                .try_build(keyset),
+            Err(BuildError::DuplicateKey {
+                index: Some(2),
+                position: 1
+            })
+        );
+        let da_bytes = DoubleArrayBuilder::builder()
+            .sort_input(true)
+            .check_duplicates(false)
+            .build(keyset)
+            .unwrap();
+        assert_eq!(
+            DoubleArray::new(da_bytes).entries().collect::<Vec<_>>(),
+            vec![(b"a".to_vec(), 0), (b"b".to_vec(), 2), (b"c".to_vec(), 3)]
+        );
+    }
+
+    #[test]
+    fn test_build_with_terminator() {
+        // keys don't have to be sorted, and indices in errors are positions in the keyset
+        let keyset: &[(&[u8], u32)] = &[(b"b", 0), (b"a\0", 1), (b"a\xFF", 2)];
+        assert_eq!(
+            DoubleArrayBuilder::builder()
+                .terminator(0xFF)
+                .try_build(keyset),
+            Err(BuildError::NullCharacter {
+                index: Some(2),
+                position: 1
+            })
+        );
+        let keyset: &[(&[u8], u32)] = &[(b"b", 0), (b"a\0", 1), (b"", 2), (b"a\0", 3)];
+        assert_eq!(
+            DoubleArrayBuilder::builder()
+                .terminator(0xFF)
+                .try_build(keyset),
+            Err(BuildError::DuplicateKey {
+                index: Some(3),
+                position: 2
+            })
+        );
+        let keyset: &[(&[u8], u32)] = &[(b"b", 0), (b"a\0", 1), (b"", 1 << 31)];
+        assert_eq!(
+            DoubleArrayBuilder::builder()
+                .terminator(0xFF)
+                .try_build(keyset),
+            Err(BuildError::ValueTooLarge {
+                index: Some(2),
+                value: 1 << 31
+            })
+        );
+
+        let keyset: &[(&[u8], u32)] = &[(b"b", 0), (b"a\0", 1), (b"a\0", 2)];
+        let da_bytes = DoubleArrayBuilder::builder()
+            .terminator(0xFF)
+            .check_duplicates(false)
+            .build(keyset)
+            .unwrap();
+        let da = DoubleArray::new(da_bytes).with_terminator::<0xFF>();
+        assert_eq!(
+            da.entries().collect::<Vec<_>>(),
+            vec![(b"a\0".to_vec(), 2), (b"b".to_vec(), 0)]
+        );
+
+        // NULL as the terminator builds the same double array as the default
+        let keyset = &[("a", 0), ("ab", 1), ("b", 2)];
+        assert_eq!(
+            DoubleArrayBuilder::builder().terminator(0).build(keyset),
+            DoubleArrayBuilder::build(keyset)
+        );
+    }
+
+    #[test]
+    fn test_build_with_dedup() {
+        let keyset = &[("a", 0), ("ab", 1), ("ab", 1), ("ab", 1), ("b", 2)];
+        assert_eq!(
+            DoubleArrayBuilder::try_build(keyset),
+            Err(BuildError::DuplicateKey {
+                index: Some(2),
+                position: 2
+            })
+        );
+        let mut builder = DoubleArrayBuilder::new().with_dedup(true);
+        let da = DoubleArray::new(builder.build_from_keyset(keyset).unwrap());
+        assert_eq!(
+            da.entries().collect::<Vec<_>>(),
+            vec![(b"a".to_vec(), 0), (b"ab".to_vec(), 1), (b"b".to_vec(), 2)]
+        );
+        assert_eq!(
+            DoubleArrayBuilder::builder().dedup(true).build(keyset),
+            Some(da.0)
+        );
+
+        let keyset = &[("a", 0), ("ab", 1), ("ab", 1), ("ab", 3), ("b", 2)];
+        let mut builder = DoubleArrayBuilder::new().with_dedup(true);
+        assert_eq!(
+            builder.try_build_from_keyset(keyset),
+            Err(BuildError::DuplicateKey {
+                index: Some(3),
+                position: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_reset() {
+        let keyset_a = &[("a", 0), ("ab", 1), ("abc", 2)];
+        let keyset_b = &[("b", 3), ("bc", 4)];
+
+        let mut builder = DoubleArrayBuilder::new();
+        assert_eq!(
+            builder.build_from_keyset(keyset_a),
+            DoubleArrayBuilder::build(keyset_a)
+        );
+        builder.reset();
+        assert_eq!(
+            builder.build_from_keyset(keyset_b),
+            DoubleArrayBuilder::build(keyset_b)
+        );
+    }
+
+    #[test]
+    fn test_build_from_sorted_iter() {
+        let keys = ["a", "ab", "abc", "b", "bc"];
+
+        let da_bytes = DoubleArrayBuilder::build_from_sorted_iter(keys.iter().zip(0..));
+        assert!(da_bytes.is_some());
+
+        let da = DoubleArray::new(da_bytes.unwrap());
+        for (value, key) in keys.iter().enumerate() {
+            assert_eq!(da.exact_match_search(key), Some(value as u32));
         }
-        assert!(!self.block.is_used.iter().all(|is_used| *is_used)); // assert not full
+        assert_eq!(da.exact_match_search("c"), None);
+    }
 
-        loop {
-            assert!(!self.block.is_used[self.unused_id as usize]);
+    #[test]
+    fn test_build_from_btreemap() {
+        let mut map = BTreeMap::new();
+        map.insert("b".to_string(), 3);
+        map.insert("a".to_string(), 0);
+        map.insert("abc".to_string(), 2);
+        map.insert("ab".to_string(), 1);
 
-            let first_label = *self.labels.first()?;
-            let offset = self.unused_id ^ first_label;
+        let da_bytes = DoubleArrayBuilder::build_from_btreemap(&map);
+        assert!(da_bytes.is_some());
 
-            let is_valid_offset = self.is_valid_offset(offset);
+        let da = DoubleArray::new(da_bytes.unwrap());
+        for (key, value) in map.iter() {
+            assert_eq!(da.exact_match_search(key), Some(*value));
+        }
+        assert_eq!(da.exact_match_search("c"), None);
+    }
 
-            // update unused_id to next unused node
-            self.unused_id = self.block.next_unused[self.unused_id as usize];
+    #[test]
+    fn test_build_from_iter() {
+        let keyset = vec![
+            ("b".to_string(), 3),
+            ("a".to_string(), 0),
+            ("ab".to_string(), 1),
+            ("b".to_string(), 4),
+        ];
 
-            if is_valid_offset {
-                return Some(offset);
+        let da = DoubleArray::new(DoubleArrayBuilder::build_from_iter(keyset).unwrap());
+        assert_eq!(da.exact_match_search("a"), Some(0));
+        assert_eq!(da.exact_match_search("ab"), Some(1));
+        assert_eq!(da.exact_match_search("b"), Some(4));
+        assert_eq!(da.entries().count(), 3);
+
+        let keyset = vec![("a", 0), ("b", 1)];
+        assert_eq!(
+            DoubleArrayBuilder::build_from_iter(keyset.clone()),
+            DoubleArrayBuilder::build(&keyset)
+        );
+    }
+
+    #[test]
+    fn test_are_unused_bitwise() {
+        // compare the bit-parallel check with the scalar check over random blocks and labels
+        let mut seed = 12345u32;
+        let mut rand = || {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 16) as u8
+        };
+        for _ in 0..100 {
+            let mut block = DoubleArrayBlock::new(0);
+            let num_used = rand();
+            for _ in 0..num_used {
+                block.reserve(rand());
             }
+            let num_labels = rand() / 4;
+            let mut labels = (0..num_labels).map(|_| rand()).collect::<Vec<_>>();
+            labels.sort_unstable();
+            labels.dedup();
+            let label_bits = to_bits(labels.iter());
 
-            if self.unused_id == INVALID_NEXT {
-                return None;
+            for offset in 0..=255u8 {
+                assert_eq!(
+                    block.are_unused_bitwise(offset, &label_bits),
+                    block.are_unused(offset, labels.iter())
+                );
             }
         }
     }
-}
 
-impl std::fmt::Debug for DoubleArrayBlock {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        f.debug_struct("DoubleArrayBlock")
-            .field(
-                "units",
-                &format_args!(
-                    "[{}]",
-                    self.units
-                        .iter()
-                        .map(|u| u.to_string())
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                ),
-            )
-            .field(
-                "is_used",
-                &format_args!(
-                    "[{}]",
-                    self.is_used
-                        .iter()
-                        .map(|u| u.to_string())
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                ),
-            )
-            .field("head_unused", &self.head_unused)
-            .field(
-                "next_unused",
-                &format_args!(
-                    "[{}]",
-                    self.next_unused
-                        .iter()
-                        .map(|u| u.to_string())
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                ),
-            )
-            .field(
-                "prev_unused",
-                &format_args!(
-                    "[{}]",
-                    self.prev_unused
-                        .iter()
-                        .map(|u| u.to_string())
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                ),
-            )
-            .finish()
-    }
-}
+    #[test]
+    fn test_from_existing() {
+        let base = &[("b", 0), ("ba", 1), ("c", 2)];
+        let da_bytes = DoubleArrayBuilder::build(base).unwrap();
 
-#[cfg(test)]
-mod tests {
-    use crate::builder::DoubleArrayBuilder;
+        let mut builder = DoubleArrayBuilder::from_existing(&da_bytes).unwrap();
+        let appended = &[("a", 3), ("bb", 4), ("d", 5), ("da", 6)];
+        assert_eq!(builder.append_sorted(appended), Ok(()));
+        assert_eq!(
+            builder.append_sorted(&[("f", 7), ("e", 8)]),
+            Err(BuildError::UnsortedKeys { index: 1 })
+        );
+        assert_eq!(
+            builder.append_sorted(&[("c", 9)]),
+            Err(BuildError::DuplicateKey {
+                index: Some(0),
+                position: 1
+            })
+        );
+
+        let da = DoubleArray::new(builder.freeze());
+        for (key, value) in base.iter().chain(appended) {
+            assert_eq!(da.exact_match_search(key), Some(*value));
+        }
+        assert_eq!(da.entries().count(), base.len() + appended.len());
+
+        let headered = DoubleArrayBuilder::build_with_header(base).unwrap();
+        let mut builder = DoubleArrayBuilder::from_existing(&headered).unwrap();
+        assert_eq!(builder.insert("a", 3), Ok(()));
+        let da = DoubleArray::new(builder.freeze());
+        assert_eq!(da.exact_match_search("a"), Some(3));
+        assert_eq!(da.exact_match_search("ba"), Some(1));
+
+        // add many keys which interleave with existing keys
+        let keyset = (0..2000u32)
+            .map(|i| (format!("{:04}", i), i))
+            .collect::<Vec<_>>();
+        let base = keyset.iter().step_by(2).cloned().collect::<Vec<_>>();
+        let appended = keyset
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .cloned()
+            .collect::<Vec<_>>();
+        let da_bytes = DoubleArrayBuilder::build(&base).unwrap();
+        let mut builder = DoubleArrayBuilder::from_existing(&da_bytes).unwrap();
+        assert_eq!(builder.append_sorted(&appended), Ok(()));
+        let da = DoubleArray::new(builder.freeze());
+        assert_eq!(
+            da.reconstruct_keyset(),
+            keyset
+                .iter()
+                .map(|(k, v)| (k.as_bytes().to_vec(), *v))
+                .collect::<Vec<_>>()
+        );
+
+        assert!(DoubleArrayBuilder::from_existing(&da_bytes[..3]).is_err());
+        assert!(DoubleArrayBuilder::from_existing(&0u32.to_le_bytes()).is_err());
+    }
 
     #[test]
-    fn test_build() {
-        let keyset: &[(&[u8], u32)] = &[
-            ("a".as_bytes(), 0),
-            ("aa".as_bytes(), 0),
-            ("aaa".as_bytes(), 0),
-            ("aaaa".as_bytes(), 0),
-            ("aaaaa".as_bytes(), 0),
-            ("ab".as_bytes(), 0),
-            ("abc".as_bytes(), 0),
-            ("abcd".as_bytes(), 0),
-            ("abcde".as_bytes(), 0),
-            ("abcdef".as_bytes(), 0),
-        ];
+    fn test_insert() {
+        let mut keyset = (0..2000u32)
+            .map(|i| (format!("{}", i.wrapping_mul(2654435761) % 100000), i))
+            .collect::<Vec<_>>();
+        keyset.sort_by(|a, b| a.0.cmp(&b.0));
+        keyset.dedup_by(|a, b| a.0 == b.0);
 
+        // insert keys in an unsorted order
         let mut builder = DoubleArrayBuilder::new();
-        let da = builder.build_from_keyset(keyset);
-        assert!(da.is_some());
+        for (key, value) in keyset.iter().rev().step_by(2) {
+            assert_eq!(builder.insert(key, *value), Ok(()));
+        }
+        for (key, value) in keyset.iter().step_by(2) {
+            assert_eq!(builder.insert(key, *value), Ok(()));
+        }
+        assert_eq!(
+            builder.insert("0", 0),
+            Err(BuildError::DuplicateKey {
+                index: None,
+                position: 1
+            })
+        );
+        assert_eq!(
+            builder.insert("0", 0).unwrap_err().to_string(),
+            "key has already been inserted"
+        );
+        assert_eq!(
+            builder.insert("a\0", 0),
+            Err(BuildError::NullCharacter {
+                index: None,
+                position: 1
+            })
+        );
+        assert_eq!(
+            builder.insert("a", 1 << 31),
+            Err(BuildError::ValueTooLarge {
+                index: None,
+                value: 1 << 31
+            })
+        );
+        for block in builder.blocks.iter() {
+            let num_unused = (0..=255u8).filter(|&id| !block.is_used(id)).count();
+            assert_eq!(block.num_unused, num_unused);
+        }
 
-        assert!(0 < builder.num_units());
-        assert!(0 < builder.num_used_units());
-        assert!(builder.num_used_units() < builder.num_units());
+        let da = DoubleArray::new(builder.freeze());
+        for (key, value) in keyset.iter() {
+            assert_eq!(da.exact_match_search(key), Some(*value));
+        }
+        assert_eq!(da.exact_match_search("100000"), None);
+        assert_eq!(
+            da.common_prefix_search("10001").count(),
+            keyset
+                .iter()
+                .filter(|(key, _)| "10001".starts_with(key.as_str()))
+                .count()
+        );
     }
 }
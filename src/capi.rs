@@ -0,0 +1,164 @@
+//! C API to embed double arrays in non-Rust hosts.
+//!
+//! Build the crate as a shared library with the `capi` feature, e.g.
+//! `cargo rustc --release --features capi --crate-type cdylib`.
+//!
+//! Ownership rules:
+//!
+//! - `yada_open()` copies the given bytes, so the host may free its buffer right after the call.
+//! - A handle returned by `yada_open()` is owned by the host and must be released by
+//!   `yada_free()` exactly once. It must not be used after it's released.
+//! - Keys are borrowed only during each call.
+
+use crate::DoubleArray;
+use std::os::raw::{c_int, c_void};
+
+/// An opaque handle of a double array.
+pub struct YadaHandle(DoubleArray<Vec<u8>>);
+
+/// A callback called with `ctx`, the value and the length of each match.
+pub type YadaMatchCallback = unsafe extern "C" fn(ctx: *mut c_void, value: u32, len: usize);
+
+/// Returns a byte slice from a pointer and a length. A null pointer is allowed for an empty slice.
+unsafe fn slice<'a>(ptr: *const u8, len: usize) -> Option<&'a [u8]> {
+    if len == 0 {
+        Some(&[])
+    } else if ptr.is_null() {
+        None
+    } else {
+        Some(std::slice::from_raw_parts(ptr, len))
+    }
+}
+
+/// Loads a double array from `len` bytes at `ptr`, and returns its handle. Returns null if the
+/// bytes are not valid as a double array, e.g. the length is not a multiple of the unit size.
+///
+/// # Safety
+///
+/// `ptr` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn yada_open(ptr: *const u8, len: usize) -> *mut YadaHandle {
+    let bytes = match slice(ptr, len) {
+        Some(bytes) => bytes.to_vec(),
+        None => return std::ptr::null_mut(),
+    };
+    match DoubleArray::from_bytes(bytes) {
+        Ok(da) => Box::into_raw(Box::new(YadaHandle(da))),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Finds a value associated with a key of `key_len` bytes at `key_ptr`. Returns 1 and writes the
+/// value to `out_value` if the key is found, 0 if not found, and -1 if arguments are null.
+///
+/// # Safety
+///
+/// `handle` must be a handle returned by `yada_open()` and not yet released, `key_ptr` must point
+/// to `key_len` readable bytes, and `out_value` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn yada_exact_match(
+    handle: *const YadaHandle,
+    key_ptr: *const u8,
+    key_len: usize,
+    out_value: *mut u32,
+) -> c_int {
+    let key = match (handle.as_ref(), slice(key_ptr, key_len)) {
+        (Some(_), Some(key)) if !out_value.is_null() => key,
+        _ => return -1,
+    };
+    match (*handle).0.exact_match_search(key) {
+        Some(value) => {
+            *out_value = value;
+            1
+        }
+        None => 0,
+    }
+}
+
+/// Finds all keys which are prefixes of a key of `key_len` bytes at `key_ptr`, and calls `cb`
+/// with `ctx` for each match in ascending order of lengths. Returns the number of matches, or -1
+/// if arguments are null.
+///
+/// # Safety
+///
+/// `handle` must be a handle returned by `yada_open()` and not yet released, and `key_ptr` must
+/// point to `key_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn yada_common_prefix(
+    handle: *const YadaHandle,
+    key_ptr: *const u8,
+    key_len: usize,
+    cb: Option<YadaMatchCallback>,
+    ctx: *mut c_void,
+) -> c_int {
+    let (handle, key, cb) = match (handle.as_ref(), slice(key_ptr, key_len), cb) {
+        (Some(handle), Some(key), Some(cb)) => (handle, key, cb),
+        _ => return -1,
+    };
+    let mut num_matches = 0;
+    for (value, len) in handle.0.common_prefix_search(key) {
+        cb(ctx, value, len);
+        num_matches += 1;
+    }
+    num_matches
+}
+
+/// Releases a handle returned by `yada_open()`. Does nothing if `handle` is null.
+///
+/// # Safety
+///
+/// `handle` must be null or a handle returned by `yada_open()` and not yet released.
+#[no_mangle]
+pub unsafe extern "C" fn yada_free(handle: *mut YadaHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::DoubleArrayBuilder;
+    use crate::capi::{yada_common_prefix, yada_exact_match, yada_free, yada_open};
+    use std::os::raw::c_void;
+
+    unsafe extern "C" fn push_match(ctx: *mut c_void, value: u32, len: usize) {
+        (*(ctx as *mut Vec<(u32, usize)>)).push((value, len));
+    }
+
+    #[test]
+    fn test_capi() {
+        let keyset = &[("a", 0), ("ab", 1), ("abc", 2)];
+        let da_bytes = DoubleArrayBuilder::build(keyset).unwrap();
+
+        unsafe {
+            let handle = yada_open(da_bytes.as_ptr(), da_bytes.len());
+            assert!(!handle.is_null());
+
+            let mut value = 0;
+            let key = b"ab";
+            assert_eq!(yada_exact_match(handle, key.as_ptr(), 2, &mut value), 1);
+            assert_eq!(value, 1);
+            assert_eq!(yada_exact_match(handle, key.as_ptr(), 0, &mut value), 0);
+            assert_eq!(
+                yada_exact_match(handle, key.as_ptr(), 2, std::ptr::null_mut()),
+                -1
+            );
+
+            let mut matches: Vec<(u32, usize)> = vec![];
+            let key = b"abcd";
+            let ctx = &mut matches as *mut _ as *mut c_void;
+            assert_eq!(
+                yada_common_prefix(handle, key.as_ptr(), 4, Some(push_match), ctx),
+                3
+            );
+            assert_eq!(matches, vec![(0, 1), (1, 2), (2, 3)]);
+            assert_eq!(yada_common_prefix(handle, key.as_ptr(), 4, None, ctx), -1);
+
+            yada_free(handle);
+            yada_free(std::ptr::null_mut());
+
+            assert!(yada_open(da_bytes.as_ptr(), 3).is_null());
+            assert!(yada_open(std::ptr::null(), 4).is_null());
+        }
+    }
+}
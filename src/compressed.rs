@@ -0,0 +1,144 @@
+//! A variable-length encoding of double arrays for distribution.
+//!
+//! Each unit is encoded as LEB128, preceded by the number of units also encoded as LEB128. Units
+//! of small tries have many zero high bytes, so the encoded bytes are smaller than the fixed 4
+//! bytes units and compress better. On the other hand, the encoded bytes can't be searched
+//! directly nor memory-mapped, and have to be decoded into a `Vec` before searching. Use it when
+//! the size of downloads matters more than loading time.
+
+use crate::builder::DoubleArrayBuilder;
+use crate::unit::UNIT_SIZE;
+use crate::{DoubleArray, DoubleArrayError};
+use std::ops::Deref;
+
+impl DoubleArrayBuilder {
+    /// Builds a double-array trie with a `keyset` like `build()`, and returns it in the
+    /// variable-length encoding. Otherwise, returns `None`. Use `DoubleArray::from_compressed()`
+    /// to decode it.
+    /// The `keyset` must be sorted.
+    pub fn build_compressed<T>(keyset: &[(T, u32)]) -> Option<Vec<u8>>
+    where
+        T: AsRef<[u8]>,
+    {
+        let da_bytes = Self::build(keyset)?;
+        Some(DoubleArray::new(da_bytes).to_compressed())
+    }
+}
+
+impl DoubleArray<Vec<u8>> {
+    /// Creates a new `DoubleArray` by decoding bytes in the variable-length encoding.
+    pub fn from_compressed(bytes: &[u8]) -> Result<Self, DoubleArrayError> {
+        let mut bytes = bytes.iter().copied();
+        let num_units = read_varint(&mut bytes).ok_or(DoubleArrayError::InvalidEncoding)?;
+        // each unit takes at least 1 byte, so don't trust a larger count before allocating
+        if num_units as usize > bytes.len() {
+            return Err(DoubleArrayError::InvalidEncoding);
+        }
+
+        let mut da_bytes = Vec::with_capacity((num_units as usize).saturating_mul(UNIT_SIZE));
+        for _ in 0..num_units {
+            let unit = read_varint(&mut bytes).ok_or(DoubleArrayError::InvalidEncoding)?;
+            da_bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+        if bytes.next().is_some() {
+            return Err(DoubleArrayError::InvalidEncoding); // trailing bytes
+        }
+        Self::from_bytes(da_bytes)
+    }
+}
+
+impl<T> DoubleArray<T>
+where
+    T: Deref<Target = [u8]>,
+{
+    /// Returns bytes of the double array in the variable-length encoding.
+    pub fn to_compressed(&self) -> Vec<u8> {
+        // the header of a headered double array is encoded as units too, so that it's restored
+        let num_units = self.0.len() / UNIT_SIZE;
+        let mut bytes = Vec::with_capacity(self.0.len());
+        write_varint(&mut bytes, num_units as u32);
+        for i in 0..num_units {
+            write_varint(&mut bytes, self.get_unit(i).unwrap().as_u32());
+        }
+        bytes
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Reads a LEB128 encoded `u32`, or returns `None` if the bytes are truncated or overflow.
+fn read_varint<I>(bytes: &mut I) -> Option<u32>
+where
+    I: Iterator<Item = u8>,
+{
+    let mut value = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte = bytes.next()?;
+        if shift == 28 && byte > 0x0F {
+            return None; // overflow
+        }
+        value |= ((byte & 0x7F) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::DoubleArrayBuilder;
+    use crate::{DoubleArray, DoubleArrayError};
+
+    #[test]
+    fn test_build_compressed() {
+        let keyset = &[("a", 0), ("ab", 1), ("abc", (1 << 31) - 1), ("b", 3)];
+        let compressed = DoubleArrayBuilder::build_compressed(keyset).unwrap();
+        let da_bytes = DoubleArrayBuilder::build(keyset).unwrap();
+        assert!(compressed.len() < da_bytes.len());
+
+        let da = DoubleArray::from_compressed(&compressed).unwrap();
+        assert_eq!(da.0, da_bytes);
+        for (key, value) in keyset {
+            assert_eq!(da.exact_match_search(key), Some(*value));
+        }
+    }
+
+    #[test]
+    fn test_from_compressed_invalid() {
+        let compressed = DoubleArrayBuilder::build_compressed(&[("a", 0)]).unwrap();
+        assert_eq!(
+            DoubleArray::from_compressed(&compressed[..compressed.len() - 1]).err(),
+            Some(DoubleArrayError::InvalidEncoding)
+        );
+
+        let mut trailing = compressed.clone();
+        trailing.push(0);
+        assert_eq!(
+            DoubleArray::from_compressed(&trailing).err(),
+            Some(DoubleArrayError::InvalidEncoding)
+        );
+
+        // 1 unit which overflows u32
+        assert_eq!(
+            DoubleArray::from_compressed(&[1, 0xFF, 0xFF, 0xFF, 0xFF, 0x1F]).err(),
+            Some(DoubleArrayError::InvalidEncoding)
+        );
+        // a huge number of units without units
+        assert_eq!(
+            DoubleArray::from_compressed(&[0xFF, 0xFF, 0xFF, 0xFF, 0x0F]).err(),
+            Some(DoubleArrayError::InvalidEncoding)
+        );
+        // no units
+        assert_eq!(
+            DoubleArray::from_compressed(&[0]).err(),
+            Some(DoubleArrayError::InvalidLength { len: 0 })
+        );
+    }
+}
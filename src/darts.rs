@@ -0,0 +1,90 @@
+//! Compatibility with the binary format of [Darts-clone](https://github.com/s-yata/darts-clone).
+//!
+//! Yada adopts the same unit layout as `Darts::DoubleArray` of Darts-clone 0.32, which is a
+//! `DoubleArrayImpl<void, void, int, void>` with 32-bit units. Files written by
+//! `Darts::DoubleArray::save()` on little-endian machines can be loaded as is, and double arrays
+//! built by yada can be loaded by `Darts::DoubleArray::open()` on little-endian machines.
+//! The original Darts (not Darts-clone) uses a different format and is not supported.
+
+use crate::header::HEADER_SIZE;
+use crate::unit::UNIT_SIZE;
+use crate::{DoubleArray, DoubleArrayError};
+use std::ops::Deref;
+
+impl DoubleArray<Vec<u8>> {
+    /// Creates a new `DoubleArray` from bytes of a Darts-clone double array.
+    pub fn from_darts_bytes(bytes: &[u8]) -> Result<Self, DoubleArrayError> {
+        // the unit layout of Darts-clone is the same as yada
+        Self::from_bytes(bytes.to_vec())
+    }
+}
+
+impl<T> DoubleArray<T>
+where
+    T: Deref<Target = [u8]>,
+{
+    /// Returns bytes of the double array in the Darts-clone format. The header of a headered
+    /// double array is stripped, because Darts-clone has no header.
+    pub fn to_darts_bytes(&self) -> Vec<u8> {
+        let header = match self.header() {
+            Some(header) => header,
+            None => return self.0.to_vec(),
+        };
+        let mut bytes = self.0[..self.0.len() - HEADER_SIZE].to_vec();
+        if header.is_big_endian() {
+            bytes.chunks_mut(UNIT_SIZE).for_each(|unit| unit.reverse());
+        }
+        bytes[0] = 0; // the label of the root is 0 in the bare format
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::DoubleArrayBuilder;
+    use crate::{DoubleArray, DoubleArrayError};
+
+    #[test]
+    fn test_from_darts_bytes() {
+        // a Darts-clone double array which has a key "a" associated with 1
+        let mut units = [0u32; 256];
+        units[0] = 1 << 10; // root node with offset 1
+        units[96] = 0x61 | 1 << 8 | 1 << 10; // label 'a' with has_leaf flag and offset 1
+        units[97] = 1 | 1 << 31; // leaf with value 1
+        let bytes = units
+            .iter()
+            .flat_map(|unit| unit.to_le_bytes().to_vec())
+            .collect::<Vec<_>>();
+
+        let da = DoubleArray::from_darts_bytes(&bytes).unwrap();
+        assert_eq!(da.exact_match_search("a"), Some(1));
+        assert_eq!(da.exact_match_search("b"), None);
+        assert_eq!(da.to_darts_bytes(), bytes);
+
+        assert_eq!(
+            DoubleArray::from_darts_bytes(&bytes[..5]).err(),
+            Some(DoubleArrayError::MisalignedLength {
+                len: 5,
+                remainder: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_darts_roundtrip() {
+        let keyset = &[("a", 0), ("ab", 1), ("b", 2)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        let da = DoubleArray::from_darts_bytes(&da.to_darts_bytes()).unwrap();
+        for (key, value) in keyset.iter() {
+            assert_eq!(da.exact_match_search(key), Some(*value));
+        }
+
+        // headers are stripped
+        let bare_bytes = DoubleArrayBuilder::build(keyset).unwrap();
+        let da = DoubleArray::new(DoubleArrayBuilder::build_with_header(keyset).unwrap());
+        assert_eq!(da.to_darts_bytes(), bare_bytes);
+        let da = DoubleArray::new(DoubleArrayBuilder::build_be(keyset).unwrap());
+        assert_eq!(da.to_darts_bytes(), bare_bytes);
+    }
+}
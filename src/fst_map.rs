@@ -0,0 +1,35 @@
+use crate::DoubleArray;
+use std::ops::Deref;
+
+impl<T> DoubleArray<T>
+where
+    T: Deref<Target = [u8]>,
+{
+    /// Converts the double array into an `fst::Map` which has the same keys and values.
+    pub fn to_fst_map(&self) -> Result<fst::Map<Vec<u8>>, fst::Error> {
+        // entries are enumerated in the byte order of keys as `fst::MapBuilder` requires
+        let mut builder = fst::MapBuilder::memory();
+        for (key, value) in self.entries() {
+            builder.insert(key, value as u64)?;
+        }
+        fst::Map::new(builder.into_inner()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::DoubleArrayBuilder;
+    use crate::DoubleArray;
+
+    #[test]
+    fn test_to_fst_map() {
+        let keyset = &[("a", 0), ("ab", 1), ("abc", 2), ("b", 3)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        let map = da.to_fst_map().unwrap();
+        assert_eq!(map.len(), keyset.len());
+        for (key, value) in keyset.iter() {
+            assert_eq!(map.get(key), Some(*value as u64));
+        }
+    }
+}
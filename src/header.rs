@@ -0,0 +1,372 @@
+//! A headered format of double arrays which stores metadata such as the number of keys.
+//!
+//! The header is appended after the units so that the units can be searched in place, and the
+//! label of the root, which is always 0 in the bare format, is set to `HEADER_FLAG` to mark the
+//! bytes as headered. Units are little-endian unless `FLAG_BIG_ENDIAN` is set, and big-endian
+//! units have to be loaded by `DoubleArray::from_headered_bytes()` before searching. The layout of
+//! the header (12 bytes) is:
+//!
+//! ```text
+//!   0..4     MAGIC      b"yada"
+//!   4        VERSION    the version of the header (1)
+//!   5        FLAGS      bit 0 is set if units are big-endian, and other bits are reserved (0)
+//!   6..8     RESERVED   0
+//!   8..12    NUM_KEYS   the number of keys as a little-endian u32
+//! ```
+
+use crate::builder::DoubleArrayBuilder;
+use crate::unit::UNIT_SIZE;
+use crate::wide::is_wide;
+use crate::{DoubleArray, DoubleArrayError};
+use std::convert::TryInto;
+use std::ops::Deref;
+
+/// The magic bytes at the beginning of the header.
+pub const HEADER_MAGIC: [u8; 4] = *b"yada";
+
+/// The current version of the header.
+pub const HEADER_VERSION: u8 = 1;
+
+/// The label of the root which marks the bytes as headered.
+pub const HEADER_FLAG: u8 = 0xDA;
+
+/// The flag which indicates that units are big-endian.
+pub const FLAG_BIG_ENDIAN: u8 = 1;
+
+/// The size of the header (12), which is a multiple of the unit size.
+pub const HEADER_SIZE: usize = 12;
+
+/// Metadata stored in the header of a headered double array.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    /// The version of the header.
+    pub version: u8,
+    /// Flags such as `FLAG_BIG_ENDIAN`.
+    pub flags: u8,
+    /// The number of keys.
+    pub num_keys: u32,
+}
+
+/// The format of serialized double arrays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrieFormat {
+    /// Units followed by a header of the `version`.
+    Headered { version: u8 },
+    /// Units only.
+    Bare,
+    /// The wide format with 64-bit units, which has to be loaded by `DoubleArray64`.
+    Wide,
+}
+
+impl Header {
+    fn to_bytes(self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0u8; HEADER_SIZE];
+        bytes[0..4].copy_from_slice(&HEADER_MAGIC);
+        bytes[4] = self.version;
+        bytes[5] = self.flags;
+        bytes[8..12].copy_from_slice(&self.num_keys.to_le_bytes());
+        bytes
+    }
+
+    /// Parses the header at the end of `bytes` if the bytes are headered. Otherwise, returns
+    /// `None`.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < UNIT_SIZE + HEADER_SIZE {
+            return None;
+        }
+        let header = &bytes[bytes.len() - HEADER_SIZE..];
+        if header[0..4] != HEADER_MAGIC {
+            return None;
+        }
+        let header = Self {
+            version: header[4],
+            flags: header[5],
+            num_keys: u32::from_le_bytes(header[8..12].try_into().unwrap()),
+        };
+        // the label of the root is the least significant byte of the first unit
+        let root_label = if header.is_big_endian() {
+            bytes[UNIT_SIZE - 1]
+        } else {
+            bytes[0]
+        };
+        if root_label != HEADER_FLAG {
+            return None;
+        }
+        Some(header)
+    }
+
+    /// Returns true if units are big-endian.
+    pub fn is_big_endian(&self) -> bool {
+        self.flags & FLAG_BIG_ENDIAN != 0
+    }
+}
+
+impl DoubleArrayBuilder {
+    /// Builds a double-array trie with a `keyset` like `build()`, and returns it with a header
+    /// that stores the number of keys. Otherwise, returns `None`.
+    /// The `keyset` must be sorted.
+    pub fn build_with_header<T>(keyset: &[(T, u32)]) -> Option<Vec<u8>>
+    where
+        T: AsRef<[u8]>,
+    {
+        if keyset.len() > u32::MAX as usize {
+            return None;
+        }
+        let da_bytes = build_for_header(keyset)?;
+        Some(with_header(da_bytes, keyset.len() as u32, 0))
+    }
+
+    /// Builds a double-array trie with a `keyset` like `build_with_header()`, but writes units in
+    /// big-endian. Use `DoubleArray::from_headered_bytes()` to load it.
+    /// The `keyset` must be sorted.
+    pub fn build_be<T>(keyset: &[(T, u32)]) -> Option<Vec<u8>>
+    where
+        T: AsRef<[u8]>,
+    {
+        if keyset.len() > u32::MAX as usize {
+            return None;
+        }
+        let mut da_bytes = build_for_header(keyset)?;
+        swap_byte_order(&mut da_bytes);
+        Some(with_header(da_bytes, keyset.len() as u32, FLAG_BIG_ENDIAN))
+    }
+}
+
+/// Builds little-endian units to be headered. The label of the root is `HEADER_FLAG` in the
+/// headered format, so a node whose absolute offset is `HEADER_FLAG` would reach the root by the
+/// byte `HEADER_FLAG`. The offset is reserved so that no node has it.
+fn build_for_header<T>(keyset: &[(T, u32)]) -> Option<Vec<u8>>
+where
+    T: AsRef<[u8]>,
+{
+    let mut builder = DoubleArrayBuilder::new();
+    builder.used_offsets.insert(HEADER_FLAG as u32);
+    builder.build_from_keyset(keyset)
+}
+
+/// Marks the root of little-endian units `da_bytes` as headered, converts them to big-endian if
+/// `flags` has `FLAG_BIG_ENDIAN`, and appends a header.
+fn with_header(mut da_bytes: Vec<u8>, num_keys: u32, flags: u8) -> Vec<u8> {
+    let root_label = if flags & FLAG_BIG_ENDIAN != 0 {
+        UNIT_SIZE - 1
+    } else {
+        0
+    };
+    da_bytes[root_label] = HEADER_FLAG;
+    let header = Header {
+        version: HEADER_VERSION,
+        flags,
+        num_keys,
+    };
+    da_bytes.extend_from_slice(&header.to_bytes());
+    da_bytes
+}
+
+/// Swaps the byte order of each unit.
+fn swap_byte_order(units: &mut [u8]) {
+    for unit in units.chunks_mut(UNIT_SIZE) {
+        unit.reverse();
+    }
+}
+
+impl DoubleArray<Vec<u8>> {
+    /// Detects the format of `bytes` without loading them, so that loaders can dispatch on it.
+    pub fn peek_format(bytes: &[u8]) -> TrieFormat {
+        if is_wide(bytes) {
+            return TrieFormat::Wide;
+        }
+        match Header::parse(bytes) {
+            Some(header) => TrieFormat::Headered {
+                version: header.version,
+            },
+            None => TrieFormat::Bare,
+        }
+    }
+
+    /// Creates a new `DoubleArray` from either headered or bare bytes, which are detected by
+    /// `peek_format()`. Returns `DoubleArrayError::WideFormat` for bytes of the wide format.
+    pub fn from_any(bytes: &[u8]) -> Result<Self, DoubleArrayError> {
+        match Self::peek_format(bytes) {
+            TrieFormat::Headered { .. } => Self::from_headered_bytes(bytes),
+            TrieFormat::Bare => Self::from_bytes(bytes.to_vec()),
+            TrieFormat::Wide => Err(DoubleArrayError::WideFormat),
+        }
+    }
+
+    /// Creates a new `DoubleArray` from headered bytes. Big-endian units are converted to
+    /// little-endian, so that the double array can be searched. Returns an error if the bytes are
+    /// not headered.
+    pub fn from_headered_bytes(bytes: &[u8]) -> Result<Self, DoubleArrayError> {
+        let header = Header::parse(bytes).ok_or(DoubleArrayError::InvalidHeader)?;
+        let units_len = bytes.len() - HEADER_SIZE;
+        let remainder = units_len % UNIT_SIZE;
+        if remainder != 0 {
+            return Err(DoubleArrayError::MisalignedLength {
+                len: bytes.len(),
+                remainder,
+            });
+        }
+        if !header.is_big_endian() {
+            return Ok(Self::new(bytes.to_vec()));
+        }
+
+        let mut units = bytes[..units_len].to_vec();
+        swap_byte_order(&mut units);
+        Ok(Self::new(with_header(
+            units,
+            header.num_keys,
+            header.flags & !FLAG_BIG_ENDIAN,
+        )))
+    }
+}
+
+impl<T, const TERM: u8> DoubleArray<T, TERM>
+where
+    T: Deref<Target = [u8]>,
+{
+    /// Returns the header if the double array is headered. Otherwise, returns `None`.
+    pub fn header(&self) -> Option<Header> {
+        Header::parse(&self.0)
+    }
+
+    /// Returns the number of keys. It takes O(1) time if the double array is headered, otherwise
+    /// it traverses all nodes.
+    pub fn len(&self) -> usize {
+        match self.header() {
+            Some(header) => header.num_keys as usize,
+            None => self.stats().num_leaves,
+        }
+    }
+
+    /// Returns true if the double array has no keys.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::DoubleArrayBuilder;
+    use crate::header::{Header, TrieFormat, HEADER_FLAG, HEADER_SIZE, HEADER_VERSION};
+    use crate::{DoubleArray, DoubleArrayError};
+
+    #[test]
+    fn test_build_with_header() {
+        let keyset = &[("a", 0), ("ab", 1), ("b", 2)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build_with_header(keyset).unwrap());
+        assert_eq!(
+            da.header(),
+            Some(Header {
+                version: HEADER_VERSION,
+                flags: 0,
+                num_keys: 3
+            })
+        );
+        assert_eq!(da.len(), 3);
+        assert!(!da.is_empty());
+        for (key, value) in keyset {
+            assert_eq!(da.exact_match_search(key), Some(*value));
+        }
+        assert_eq!(da.entries().count(), 3);
+
+        // the header is not a unit but a part of the memory
+        let bare = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+        assert_eq!(da.num_units(), bare.num_units());
+        assert_eq!(da.units()[1..], bare.units()[1..]);
+        assert_eq!(da.unit_at(da.num_units()), None);
+        assert_eq!(da.stats().num_units, da.num_units());
+        assert_eq!(da.leaves().count(), 3);
+        assert_eq!(da.memory_size(), bare.memory_size() + HEADER_SIZE);
+
+        let da = bare;
+        assert_eq!(da.header(), None);
+        assert_eq!(da.len(), 3);
+    }
+
+    #[test]
+    fn test_build_with_header_root_unreachable() {
+        // a node of these keys has the absolute offset HEADER_FLAG unless it's reserved
+        let keyset = (0..44u32)
+            .map(|i| (format!("k{:05}", i * 748), i))
+            .collect::<Vec<_>>();
+        for da_bytes in &[
+            DoubleArrayBuilder::build_with_header(&keyset).unwrap(),
+            DoubleArray::from_headered_bytes(&DoubleArrayBuilder::build_be(&keyset).unwrap())
+                .unwrap()
+                .0,
+        ] {
+            let da = DoubleArray::new(da_bytes.as_slice());
+            assert_eq!(da.validate(), Ok(()));
+            assert_eq!(da.entries().count(), keyset.len());
+            for (key, _) in keyset.iter() {
+                for len in 0..=key.len() {
+                    let mut query = key.as_bytes()[..len].to_vec();
+                    query.push(HEADER_FLAG);
+                    query.extend_from_slice(keyset[0].0.as_bytes());
+                    assert_eq!(da.exact_match_search(&query), None);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_build_be() {
+        let keyset = &[("a", 0), ("ab", 1), ("b", 2)];
+        let be_bytes = DoubleArrayBuilder::build_be(keyset).unwrap();
+        let header = Header::parse(&be_bytes).unwrap();
+        assert!(header.is_big_endian());
+        assert_eq!(header.num_keys, 3);
+
+        let da = DoubleArray::from_headered_bytes(&be_bytes).unwrap();
+        assert!(!da.header().unwrap().is_big_endian());
+        assert_eq!(da.len(), 3);
+        for (key, value) in keyset {
+            assert_eq!(da.exact_match_search(key), Some(*value));
+        }
+        assert_eq!(da.0, DoubleArrayBuilder::build_with_header(keyset).unwrap());
+
+        let bare_bytes = DoubleArrayBuilder::build(keyset).unwrap();
+        assert_eq!(
+            DoubleArray::from_headered_bytes(&bare_bytes).err(),
+            Some(DoubleArrayError::InvalidHeader)
+        );
+    }
+
+    #[test]
+    fn test_from_any() {
+        let keyset = &[("a", 0), ("ab", 1), ("b", 2)];
+        let bare_bytes = DoubleArrayBuilder::build(keyset).unwrap();
+        let headered_bytes = DoubleArrayBuilder::build_with_header(keyset).unwrap();
+        let be_bytes = DoubleArrayBuilder::build_be(keyset).unwrap();
+
+        assert_eq!(DoubleArray::peek_format(&bare_bytes), TrieFormat::Bare);
+        for bytes in &[&headered_bytes, &be_bytes] {
+            assert_eq!(
+                DoubleArray::peek_format(bytes),
+                TrieFormat::Headered {
+                    version: HEADER_VERSION
+                }
+            );
+        }
+
+        for bytes in &[&bare_bytes, &headered_bytes, &be_bytes] {
+            let da = DoubleArray::from_any(bytes).unwrap();
+            for (key, value) in keyset {
+                assert_eq!(da.exact_match_search(key), Some(*value));
+            }
+        }
+        let wide_bytes = DoubleArrayBuilder::build_wide(keyset).unwrap();
+        assert_eq!(DoubleArray::peek_format(&wide_bytes), TrieFormat::Wide);
+        assert_eq!(
+            DoubleArray::from_any(&wide_bytes).err(),
+            Some(DoubleArrayError::WideFormat)
+        );
+        assert_eq!(
+            DoubleArray::from_any(&bare_bytes[..3]).err(),
+            Some(DoubleArrayError::MisalignedLength {
+                len: 3,
+                remainder: 3
+            })
+        );
+    }
+}
@@ -1,13 +1,112 @@
+pub mod alphabet;
 pub mod builder;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod compressed;
+pub mod darts;
+#[cfg(feature = "fst")]
+mod fst_map;
+pub mod header;
+pub mod merge;
+pub mod tail;
+pub mod transducer;
 pub mod unit;
+pub mod validate;
+pub mod values;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod wide;
 
 use crate::unit::{Unit, UnitID, UNIT_SIZE};
+use crate::validate::ValidationError;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashSet;
 use std::convert::TryInto;
+use std::io::{self, Read};
 use std::ops::Deref;
 
+/// An error that occurs while loading a double array.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DoubleArrayError {
+    /// The length of bytes is invalid, e.g. there are no units.
+    InvalidLength { len: usize },
+    /// The length of bytes is not a multiple of the unit size, e.g. the bytes are truncated in the
+    /// middle of a unit. `remainder` is the number of trailing bytes.
+    MisalignedLength { len: usize, remainder: usize },
+    /// The bytes don't start with the expected header.
+    InvalidHeader,
+    /// The bytes are not valid in the variable-length encoding.
+    InvalidEncoding,
+    /// The structure of the double array is broken.
+    InvalidStructure(ValidationError),
+    /// The bytes are in the wide format, which has to be loaded by `DoubleArray64`.
+    WideFormat,
+}
+
+impl std::fmt::Display for DoubleArrayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DoubleArrayError::InvalidLength { len } => {
+                write!(f, "invalid length of double array bytes: {}", len)
+            }
+            DoubleArrayError::MisalignedLength { len, remainder } => write!(
+                f,
+                "misaligned length of double array bytes: {} ({} trailing bytes)",
+                len, remainder
+            ),
+            DoubleArrayError::InvalidHeader => write!(f, "invalid header of double array bytes"),
+            DoubleArrayError::InvalidEncoding => {
+                write!(f, "invalid encoding of double array bytes")
+            }
+            DoubleArrayError::InvalidStructure(err) => {
+                write!(f, "invalid structure of double array: {}", err)
+            }
+            DoubleArrayError::WideFormat => {
+                write!(f, "double array bytes are in the wide format")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DoubleArrayError {}
+
+/// Statistics of a double array trie.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DoubleArrayStats {
+    /// The number of units, which doesn't count the header of a headered double array.
+    pub num_units: usize,
+    /// The number of leaves, which equals to the number of keys.
+    pub num_leaves: usize,
+    /// The maximum depth of leaves, which equals to the length of the longest key.
+    pub max_depth: usize,
+}
+
+/// A match of a key found by a search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Match {
+    /// The value associated with the matched key.
+    pub value: u32,
+    /// The length of the matched key in bytes.
+    pub len: usize,
+}
+
+impl From<Match> for (u32, usize) {
+    fn from(m: Match) -> Self {
+        (m.value, m.len)
+    }
+}
+
 /// A double array trie.
+///
+/// Searches take `&self`, so a `DoubleArray` can be shared between threads (e.g. with `Arc`) and
+/// searched concurrently if `T` is `Send` and `Sync`.
+///
+/// Keys are terminated by `TERM`, which is NULL (`'\0'`) by default, so keys can't contain it.
+/// A double array built by `BuildConfig::terminator()` with another byte has to be searched with
+/// the same `TERM`, which `with_terminator()` sets.
 #[derive(Clone)]
-pub struct DoubleArray<T>(pub T)
+pub struct DoubleArray<T, const TERM: u8 = 0>(pub T)
 where
     T: Deref<Target = [u8]>;
 
@@ -17,7 +116,42 @@ where
 {
     /// Creates a new `DoubleArray` with a byte slice.
     pub fn new(bytes: T) -> Self {
-        Self { 0: bytes }
+        Self(bytes)
+    }
+
+    /// Creates a new `DoubleArray` with a byte slice if the length of the bytes is a positive
+    /// multiple of the unit size. Otherwise, returns an error.
+    pub fn from_bytes(bytes: T) -> Result<Self, DoubleArrayError> {
+        if bytes.is_empty() {
+            return Err(DoubleArrayError::InvalidLength { len: 0 });
+        }
+        if wide::is_wide(&bytes) {
+            return Err(DoubleArrayError::WideFormat);
+        }
+        let remainder = bytes.len() % UNIT_SIZE;
+        if remainder != 0 {
+            return Err(DoubleArrayError::MisalignedLength {
+                len: bytes.len(),
+                remainder,
+            });
+        }
+        Ok(Self(bytes))
+    }
+
+    /// Converts the double array into one whose keys are terminated by `TERM` instead of NULL,
+    /// which is built by `BuildConfig::terminator(TERM)`.
+    pub fn with_terminator<const TERM: u8>(self) -> DoubleArray<T, TERM> {
+        DoubleArray(self.0)
+    }
+}
+
+impl<T, const TERM: u8> DoubleArray<T, TERM>
+where
+    T: Deref<Target = [u8]>,
+{
+    /// Returns the terminator of keys.
+    pub fn terminator(&self) -> u8 {
+        TERM
     }
 
     /// Finds a value associated with a `key`.
@@ -25,17 +159,109 @@ where
     where
         K: AsRef<[u8]>,
     {
-        self.exact_match_search_bytes(key.as_ref())
+        self.exact_match_search_iter(key.as_ref().iter().copied())
+    }
+
+    /// Finds a value associated with a `key` like `exact_match_search()`, but returns `default` if
+    /// the `key` is not found.
+    pub fn get_or<K>(&self, key: K, default: u32) -> u32
+    where
+        K: AsRef<[u8]>,
+    {
+        self.exact_match_search(key).unwrap_or(default)
+    }
+
+    /// Finds a value associated with a `key` like `exact_match_search()` for lookups which never
+    /// miss, such as in tests.
+    ///
+    /// This is a method rather than an `Index` implementation, because `Index` has to return a
+    /// reference and values are packed into units with flags, so there is no `u32` to borrow.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `key` is not found.
+    pub fn value_of<K>(&self, key: K) -> u32
+    where
+        K: AsRef<[u8]>,
+    {
+        let key = key.as_ref();
+        match self.exact_match_search(key) {
+            Some(value) => value,
+            None => panic!("key not found: {:?}", String::from_utf8_lossy(key)),
+        }
+    }
+
+    /// Finds a value associated with a `key` like `exact_match_search()`, and returns it with the
+    /// length of the `key` in the same shape as `common_prefix_search()`.
+    pub fn exact_match_search_with_len<K>(&self, key: K) -> Option<(u32, usize)>
+    where
+        K: AsRef<[u8]>,
+    {
+        let key = key.as_ref();
+        let value = self.exact_match_search(key)?;
+        Some((value, key.len()))
+    }
+
+    /// Finds a signed value associated with a `key` in a double array built by
+    /// `DoubleArrayBuilder::build_i32()`. The stored 31 bits value is sign-extended to `i32`.
+    pub fn exact_match_search_i32<K>(&self, key: K) -> Option<i32>
+    where
+        K: AsRef<[u8]>,
+    {
+        let value = self.exact_match_search_iter(key.as_ref().iter().copied())?;
+        Some(((value << 1) as i32) >> 1)
+    }
+
+    /// Finds the ordinal of a `key` in a double array built by
+    /// `DoubleArrayBuilder::build_ordinals()`, which is in `0..n` for `n` keys.
+    pub fn ordinal_of<K>(&self, key: K) -> Option<usize>
+    where
+        K: AsRef<[u8]>,
+    {
+        let ordinal = self.exact_match_search(key)?;
+        Some(ordinal as usize)
+    }
+
+    /// Finds a value associated with a key whose bytes are pulled from an iterator. It returns
+    /// `None` as soon as a transition fails without consuming the rest of the bytes.
+    #[inline]
+    pub fn exact_match_search_iter<I>(&self, bytes: I) -> Option<u32>
+    where
+        I: IntoIterator<Item = u8>,
+    {
+        let leaf = self.exact_match_leaf(bytes)?;
+        Some(leaf.value())
+    }
+
+    /// Finds the leaf of a `key` like `exact_match_search()`, but returns the raw 32 bits of the
+    /// leaf unit including the leaf flag instead of the value, so that the bit layout can be
+    /// interpreted by other tools.
+    pub fn exact_match_raw<K>(&self, key: K) -> Option<u32>
+    where
+        K: AsRef<[u8]>,
+    {
+        let leaf = self.exact_match_leaf(key.as_ref().iter().copied())?;
+        Some(leaf.as_u32())
     }
 
-    fn exact_match_search_bytes(&self, key: &[u8]) -> Option<u32> {
+    #[inline(always)]
+    fn exact_match_leaf<I>(&self, bytes: I) -> Option<Unit>
+    where
+        I: IntoIterator<Item = u8>,
+    {
         // traverse from root node
         let mut node_pos = 0 as UnitID;
         let mut unit = self.get_unit(node_pos)?;
 
-        for &c in key.iter().take(key.len()) {
-            assert!(!unit.is_leaf());
-            assert_ne!(c, 0); // assumes characters don't have NULL ('\0')
+        for c in bytes {
+            if unit.is_leaf() {
+                return None;
+            }
+            // bytes are relabeled so that the terminator is 0, which stored keys never contain
+            let c = c ^ TERM;
+            if c == 0 {
+                return None;
+            }
 
             // try to traverse node
             node_pos = (unit.offset() ^ node_pos as u32 ^ c as u32) as UnitID;
@@ -50,20 +276,21 @@ where
             return None;
         }
 
-        // traverse node by NULL ('\0')
-        let node_pos = (unit.offset() ^ node_pos as u32 ^ 0u32) as UnitID;
+        // traverse node by the terminator
+        let node_pos = (unit.offset() ^ node_pos as u32) as UnitID;
         unit = self.get_unit(node_pos)?;
-        assert!(unit.is_leaf());
-        assert!(unit.value() < (1 << 31));
+        if !unit.is_leaf() {
+            return None;
+        }
 
-        Some(unit.value())
+        Some(unit)
     }
 
     /// Finds all values and it's key length which have a common prefix with a `key`.
-    pub fn common_prefix_search<'b, K>(
-        &'b self,
-        key: &'b K,
-    ) -> impl Iterator<Item = (u32, usize)> + 'b
+    pub fn common_prefix_search<'d, 'k, K>(
+        &'d self,
+        key: &'k K,
+    ) -> CommonPrefixSearch<'k, 'd, T, TERM>
     where
         K: AsRef<[u8]>,
         K: ?Sized,
@@ -71,158 +298,1785 @@ where
         self.common_prefix_search_bytes(key.as_ref())
     }
 
-    fn common_prefix_search_bytes<'b>(
-        &'b self,
-        key: &'b [u8],
-    ) -> impl Iterator<Item = (u32, usize)> + 'b {
-        CommonPrefixSearch {
-            key,
+    /// Folds all values and it's key length which have a common prefix with a `key` into an
+    /// accumulator by `f`, starting from `init`. It yields the same matches as
+    /// `common_prefix_search()`, but walks the key in a single loop without an iterator, which is
+    /// slightly faster in hot loops.
+    #[inline]
+    pub fn fold_common_prefix<K, B, F>(&self, key: K, init: B, mut f: F) -> B
+    where
+        K: AsRef<[u8]>,
+        F: FnMut(B, u32, usize) -> B,
+    {
+        let mut acc = init;
+        let mut unit_id = 0 as UnitID;
+        let mut unit = match self.get_unit(unit_id) {
+            Some(unit) => unit,
+            None => return acc,
+        };
+        for (i, &c) in key.as_ref().iter().enumerate() {
+            let c = c ^ TERM;
+            unit_id = (unit.offset() ^ unit_id as u32 ^ c as u32) as UnitID;
+            unit = match self.get_unit(unit_id) {
+                Some(unit) if unit.label() == c as u32 => unit,
+                _ => break,
+            };
+            if unit.has_leaf() {
+                let leaf_pos = unit.offset() ^ unit_id as u32;
+                match self.get_unit(leaf_pos as UnitID) {
+                    Some(leaf_unit) => acc = f(acc, leaf_unit.value(), i + 1),
+                    None => break,
+                }
+            }
+        }
+        acc
+    }
+
+    /// Returns true if any key is a prefix of an `input`. It stops traversing at the first key found,
+    /// so the rest of the `input` is not read.
+    pub fn starts_with_any<K>(&self, input: K) -> bool
+    where
+        K: AsRef<[u8]>,
+    {
+        self.starts_with_any_iter(input.as_ref().iter().copied())
+    }
+
+    /// Returns true if any key is a prefix of `bytes` pulled from an iterator like
+    /// `starts_with_any()`. No more bytes are pulled once a key is found.
+    pub fn starts_with_any_iter<I>(&self, bytes: I) -> bool
+    where
+        I: Iterator<Item = u8>,
+    {
+        self.common_prefix_search_iter(bytes).next().is_some()
+    }
+
+    /// Finds all keys which are prefixes of a `key` like `common_prefix_search()`, but returns
+    /// `Match`es instead of tuples.
+    pub fn common_prefix_matches<'b, K>(&'b self, key: &'b K) -> impl Iterator<Item = Match> + 'b
+    where
+        K: AsRef<[u8]>,
+        K: ?Sized,
+    {
+        self.common_prefix_search_bytes(key.as_ref())
+            .map(|(value, len)| Match { value, len })
+    }
+
+    /// Finds all values and matched prefixes of a string `s` like `common_prefix_search()`.
+    /// Matches that don't end on a UTF-8 char boundary of `s` are skipped, so the prefixes are
+    /// always valid slices of `s`.
+    pub fn common_prefix_search_str<'a>(
+        &'a self,
+        s: &'a str,
+    ) -> impl Iterator<Item = (u32, &'a str)> + 'a {
+        self.common_prefix_search_bytes(s.as_bytes())
+            .filter(move |&(_, len)| s.is_char_boundary(len))
+            .map(move |(value, len)| (value, &s[..len]))
+    }
+
+    /// Segments `text` by the longest match, and returns an iterator over tuples of the start
+    /// position, the end position and the value of each matched key. When no key matches at a
+    /// position, the byte is skipped without emitting a token.
+    pub fn tokenize_greedy<'a>(
+        &'a self,
+        text: &'a [u8],
+    ) -> impl Iterator<Item = (usize, usize, u32)> + 'a {
+        let mut start = 0;
+        std::iter::from_fn(move || {
+            while start < text.len() {
+                let longest = self.common_prefix_search_bytes(&text[start..]).last();
+                match longest {
+                    Some((value, len)) => {
+                        let token = (start, start + len, value);
+                        start += len;
+                        return Some(token);
+                    }
+                    None => start += 1, // unknown byte
+                }
+            }
+            None
+        })
+    }
+
+    /// Finds all occurrences of keys in `text` including overlapping ones in a single pass, and
+    /// returns an iterator over tuples of the start position, the end position and the value of
+    /// each match. Matches are ordered by the start position, and then by the end position.
+    /// A common prefix search is started at each position, so it takes O(nm) time for the length
+    /// `n` of the `text` and the length `m` of the longest key.
+    pub fn find_overlapping<'a>(
+        &'a self,
+        text: &'a [u8],
+    ) -> impl Iterator<Item = (usize, usize, u32)> + 'a {
+        (0..text.len()).flat_map(move |start| {
+            self.common_prefix_search_bytes(&text[start..])
+                .map(move |(value, len)| (start, start + len, value))
+        })
+    }
+
+    /// Finds values and it's key length which have a common prefix with a `key` like
+    /// `common_prefix_search()`, writes them to `out`, and returns the number of written matches.
+    /// Matches beyond `out.len()` are dropped.
+    pub fn common_prefix_search_into<K>(&self, key: K, out: &mut [(u32, usize)]) -> usize
+    where
+        K: AsRef<[u8]>,
+    {
+        let mut count = 0;
+        for (slot, item) in out
+            .iter_mut()
+            .zip(self.common_prefix_search_bytes(key.as_ref()))
+        {
+            *slot = item;
+            count += 1;
+        }
+        count
+    }
+
+    /// Finds all values and it's key length which have a common prefix with `bytes` like
+    /// `common_prefix_search()`. The bytes are pulled lazily from the iterator, and no more bytes
+    /// are pulled once a transition fails.
+    pub fn common_prefix_search_iter<I>(&self, bytes: I) -> CommonPrefixSearchIter<'_, T, I, TERM>
+    where
+        I: Iterator<Item = u8>,
+    {
+        CommonPrefixSearchIter {
+            bytes,
             double_array: self,
             unit_id: 0,
             key_pos: 0,
+            finished: false,
         }
     }
 
-    #[inline(always)]
-    fn get_unit(&self, index: usize) -> Option<Unit> {
-        let b = unsafe {
-            // This unsafe method call does not lead unexpected transitions
-            // when a double array was built properly.
-            self.0
-                .get_unchecked(index * UNIT_SIZE..(index + 1) * UNIT_SIZE)
-        };
-        match b.try_into() {
-            Ok(bytes) => Some(Unit::from_u32(u32::from_le_bytes(bytes))),
-            Err(_) => None,
+    /// Finds all values and it's key length which have a common prefix with bytes read from `r`.
+    /// Bytes are read one by one, so no more bytes are read once a transition fails. Returns an
+    /// I/O error if reading fails. Wrap an unbuffered reader such as `File` in `BufReader`.
+    #[allow(clippy::unbuffered_bytes)] // reading more bytes than necessary is not allowed
+    pub fn common_prefix_search_read<R>(&self, r: &mut R) -> io::Result<Vec<(u32, usize)>>
+    where
+        R: Read,
+    {
+        let mut error = None;
+        let results = self
+            .common_prefix_search_iter(r.bytes().scan(&mut error, |error, byte| match byte {
+                Ok(byte) => Some(byte),
+                Err(e) => {
+                    **error = Some(e);
+                    None
+                }
+            }))
+            .collect();
+
+        match error {
+            Some(e) => Err(e),
+            None => Ok(results),
         }
     }
-}
 
-/// An iterator that finds all values with a common prefix.
-pub struct CommonPrefixSearch<'k, 'd, T>
-where
-    T: Deref<Target = [u8]>,
-{
-    key: &'k [u8],
-    double_array: &'d DoubleArray<T>,
-    unit_id: UnitID,
-    key_pos: usize,
-}
+    /// Finds all values and it's key length like `common_prefix_search()`, but starts from a
+    /// `node` instead of the root, so that a prefix already traversed isn't traversed again. The
+    /// `node` must be a valid non-leaf position, e.g. one returned by
+    /// `Transducer::accept_bytes()`, and the lengths are counted from the `node`. A leaf or an
+    /// out-of-bounds `node` yields nothing.
+    pub fn common_prefix_search_from<'d, 'k>(
+        &'d self,
+        node: UnitID,
+        key: &'k [u8],
+    ) -> CommonPrefixSearch<'k, 'd, T, TERM> {
+        let key = match self.unit_at(node) {
+            Some(unit) if !unit.is_leaf() => key,
+            _ => &[],
+        };
+        CommonPrefixSearch {
+            key,
+            double_array: self,
+            unit_id: node,
+            key_pos: 0,
+        }
+    }
 
-impl<T> Iterator for CommonPrefixSearch<'_, '_, T>
-where
-    T: Deref<Target = [u8]>,
-{
-    type Item = (u32, usize);
+    fn common_prefix_search_bytes<'d, 'k>(
+        &'d self,
+        key: &'k [u8],
+    ) -> CommonPrefixSearch<'k, 'd, T, TERM> {
+        CommonPrefixSearch {
+            key,
+            double_array: self,
+            unit_id: 0,
+            key_pos: 0,
+        }
+    }
 
-    #[inline(always)]
-    fn next(&mut self) -> Option<Self::Item> {
-        while self.key_pos < self.key.len() {
-            let unit = self.double_array.get_unit(self.unit_id)?;
+    /// Returns the number of leading bytes of a `key` which can be traversed from the root,
+    /// regardless of whether the traversed bytes are a key or not.
+    pub fn longest_traversable_prefix_len<K>(&self, key: K) -> usize
+    where
+        K: AsRef<[u8]>,
+    {
+        self.traverse(key.as_ref()).1
+    }
 
-            let c = *self.key.get(self.key_pos)?;
-            self.key_pos += 1;
+    /// Returns the number of keys which start with a `prefix`. Returns 0 if no key starts with
+    /// the `prefix`.
+    ///
+    /// Subtree counts are not stored in the double array, so this takes time proportional to the
+    /// size of the subtree under the `prefix`.
+    pub fn count_keys_with_prefix<K>(&self, prefix: K) -> usize
+    where
+        K: AsRef<[u8]>,
+    {
+        let prefix = prefix.as_ref();
+        let (node_pos, len) = self.traverse(prefix);
+        if len < prefix.len() {
+            return 0;
+        }
 
-            self.unit_id = (unit.offset() ^ self.unit_id as u32 ^ c as u32) as UnitID;
-            let unit = self.double_array.get_unit(self.unit_id)?;
-            if unit.label() != c as u32 {
-                return None;
+        let mut count = 0;
+        let mut stack = vec![node_pos];
+        let mut remaining_steps = self.num_units(); // guarantees termination on corrupt bytes
+        while let Some(node_pos) = stack.pop() {
+            if remaining_steps == 0 {
+                break;
             }
-            if unit.has_leaf() {
-                let leaf_pos = unit.offset() ^ self.unit_id as u32;
-                let leaf_unit = self.double_array.get_unit(leaf_pos as UnitID)?;
-                return Some((leaf_unit.value(), self.key_pos));
+            remaining_steps -= 1;
+            for (label, child_pos) in self.children(node_pos) {
+                if label == 0 {
+                    count += 1;
+                } else {
+                    stack.push(child_pos);
+                }
             }
         }
-        None
+        count
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use crate::builder::DoubleArrayBuilder;
-    use crate::DoubleArray;
-
-    #[test]
-    fn test_build_search() {
-        let keyset = &[
-            ("a".as_bytes(), 0),
-            ("ab".as_bytes(), 1),
-            ("aba".as_bytes(), 2),
-            ("ac".as_bytes(), 3),
-            ("acb".as_bytes(), 4),
-            ("acc".as_bytes(), 5),
-            ("ad".as_bytes(), 6),
-            ("ba".as_bytes(), 7),
-            ("bb".as_bytes(), 8),
-            ("bc".as_bytes(), 9),
-            ("c".as_bytes(), 10),
-            ("caa".as_bytes(), 11),
-        ];
 
-        let da_bytes = DoubleArrayBuilder::build(keyset);
-        assert!(da_bytes.is_some());
+    /// Returns true if any key starts with a `prefix`, regardless of whether the `prefix` itself
+    /// is a key or not. Unlike `count_keys_with_prefix()`, it only traverses the `prefix`.
+    pub fn contains_prefix<K>(&self, prefix: K) -> bool
+    where
+        K: AsRef<[u8]>,
+    {
+        let prefix = prefix.as_ref();
+        self.traverse(prefix).1 == prefix.len()
+    }
 
-        let da = DoubleArray::new(da_bytes.unwrap());
+    /// Traverses nodes from the root by a `key` until a transition fails, and returns the position
+    /// of the last reached node and the number of traversed bytes.
+    fn traverse(&self, key: &[u8]) -> (UnitID, usize) {
+        let mut node_pos = 0 as UnitID;
+        let mut unit = match self.get_unit(node_pos) {
+            Some(unit) => unit,
+            None => return (node_pos, 0),
+        };
 
-        for (key, value) in keyset {
-            assert_eq!(da.exact_match_search(key), Some(*value as u32));
+        for (i, &c) in key.iter().enumerate() {
+            let c = c ^ TERM;
+            if c == 0 || unit.is_leaf() {
+                return (node_pos, i);
+            }
+            let child_pos = (unit.offset() ^ node_pos as u32 ^ c as u32) as UnitID;
+            match self.get_unit(child_pos) {
+                Some(child) if child.label() == c as u32 => {
+                    node_pos = child_pos;
+                    unit = child;
+                }
+                _ => return (node_pos, i),
+            }
         }
-        assert_eq!(da.exact_match_search("aa".as_bytes()), None);
-        assert_eq!(da.exact_match_search("abc".as_bytes()), None);
-        assert_eq!(da.exact_match_search("b".as_bytes()), None);
-        assert_eq!(da.exact_match_search("ca".as_bytes()), None);
+        (node_pos, key.len())
+    }
 
-        assert_eq!(
-            da.common_prefix_search("a".as_bytes()).collect::<Vec<_>>(),
-            vec![(0, 1)]
-        );
-        assert_eq!(
-            da.common_prefix_search("aa".as_bytes()).collect::<Vec<_>>(),
-            vec![(0, 1)]
-        );
-        assert_eq!(
-            da.common_prefix_search("abbb".as_bytes())
-                .collect::<Vec<_>>(),
-            vec![(0, 1), (1, 2)]
-        );
-        assert_eq!(
-            da.common_prefix_search("abaa".as_bytes())
-                .collect::<Vec<_>>(),
-            vec![(0, 1), (1, 2), (2, 3)]
-        );
-        assert_eq!(
-            da.common_prefix_search("caa".as_bytes())
-                .collect::<Vec<_>>(),
-            vec![(10, 1), (11, 3)]
-        );
-        assert_eq!(
-            da.common_prefix_search("d".as_bytes()).collect::<Vec<_>>(),
-            vec![]
-        );
+    /// Returns an iterator over all keys and values in the double array. Keys are enumerated in
+    /// depth-first order, which equals to the byte order of keys.
+    pub fn entries(&self) -> Entries<'_, T, TERM> {
+        self.entries_from(0, &[])
     }
 
-    #[test]
-    fn test_exact_match_search_corner_case() {
-        // A corner case of `exact_match_search()`.
-        // See https://github.com/takuyaa/yada/pull/28 for more details.
-        let keyset = &[
-            ("a".as_bytes(), 97),
-            ("ab".as_bytes(), 1),
-            ("de".as_bytes(), 2),
-        ];
+    /// Returns an iterator over all keys and values like `entries()`, following the naming of
+    /// standard maps. They are enumerated in depth-first order, which equals to the byte order of
+    /// keys, not the order of values.
+    pub fn iter(&self) -> Entries<'_, T, TERM> {
+        self.entries()
+    }
 
-        let da_bytes = DoubleArrayBuilder::build(keyset);
-        assert!(da_bytes.is_some());
+    /// Returns an iterator over all keys in depth-first order like `iter()`.
+    pub fn keys(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        self.entries().map(|(key, _)| key)
+    }
 
-        let da = DoubleArray::new(da_bytes.unwrap());
+    /// Returns an iterator over values of all keys in depth-first order like `iter()`, so the
+    /// values are in the byte order of their keys.
+    pub fn values(&self) -> impl Iterator<Item = u32> + '_ {
+        self.entries().map(|(_, value)| value)
+    }
 
-        for (key, value) in keyset {
-            assert_eq!(da.exact_match_search(key), Some(*value as u32));
+    /// Returns all keys and values sorted by keys. For a double array built properly, it equals to
+    /// the sorted keyset passed to the builder.
+    pub fn reconstruct_keyset(&self) -> Vec<(Vec<u8>, u32)> {
+        // entries are enumerated in the byte order of keys
+        self.entries().collect()
+    }
+
+    /// Calls `f` with each key and value in the same order as `entries()`. Unlike `entries()`, keys
+    /// are not allocated for each entry but borrowed from a single buffer, so the key slice is only
+    /// valid for the duration of each call.
+    pub fn for_each_entry<F>(&self, mut f: F)
+    where
+        F: FnMut(&[u8], u32),
+    {
+        let mut entries = self.entries();
+        while let Some((key, value)) = entries.next_borrowed() {
+            f(key, value);
+        }
+    }
+
+    /// Returns an iterator over keys and values under the node at `node_pos` whose key is `prefix`.
+    fn entries_from(&self, node_pos: UnitID, prefix: &[u8]) -> Entries<'_, T, TERM> {
+        let mut entries = Entries {
+            double_array: self,
+            stack: vec![],
+            key: prefix.to_vec(),
+            remaining_steps: self.num_units(),
+        };
+        entries.push_children(node_pos, prefix.len());
+        entries
+    }
+
+    /// Returns an iterator over all keys which start with a `prefix` in the byte order. Keys
+    /// include the `prefix`, and an empty `prefix` enumerates all keys like `entries()`.
+    pub fn prefix_keys<K>(&self, prefix: K) -> impl Iterator<Item = Vec<u8>> + '_
+    where
+        K: AsRef<[u8]>,
+    {
+        let prefix = prefix.as_ref();
+        let (node_pos, len) = self.traverse(prefix);
+        let entries = if len < prefix.len() {
+            None
+        } else {
+            Some(self.entries_from(node_pos, prefix))
+        };
+        entries.into_iter().flatten().map(|(key, _)| key)
+    }
+
+    /// Returns at most `n` keys and values which start with a `prefix`. The results are the first
+    /// `n` ones ordered by values with `cmp`, not by keys, and keys with equal values are ordered by
+    /// keys.
+    pub fn predictive_top_n<K, F>(&self, prefix: K, n: usize, cmp: F) -> Vec<(Vec<u8>, u32)>
+    where
+        K: AsRef<[u8]>,
+        F: Fn(u32, u32) -> Ordering,
+    {
+        let prefix = prefix.as_ref();
+        let (node_pos, len) = self.traverse(prefix);
+        if len < prefix.len() || n == 0 {
+            return vec![];
+        }
+
+        // keep the best `n` entries sorted by `cmp`
+        let mut top_n: Vec<(Vec<u8>, u32)> = Vec::with_capacity(n);
+        for (key, value) in self.entries_from(node_pos, prefix) {
+            if top_n.len() == n && cmp(value, top_n[n - 1].1) != Ordering::Less {
+                continue;
+            }
+            let pos = top_n
+                .binary_search_by(|&(_, v)| match cmp(v, value) {
+                    Ordering::Equal => Ordering::Less, // insert after equal values
+                    ordering => ordering,
+                })
+                .unwrap_or_else(|pos| pos);
+            top_n.insert(pos, (key, value));
+            top_n.truncate(n);
+        }
+        top_n
+    }
+
+    /// Returns statistics of the double array by traversing all nodes reachable from the root.
+    pub fn stats(&self) -> DoubleArrayStats {
+        let mut num_leaves = 0;
+        let mut max_depth = 0;
+
+        // traverse in depth-first order
+        let mut stack = vec![(0 as UnitID, 0)];
+        let mut remaining_steps = self.num_units(); // guarantees termination on corrupt bytes
+        while let Some((node_pos, depth)) = stack.pop() {
+            if remaining_steps == 0 {
+                break;
+            }
+            remaining_steps -= 1;
+            for (label, child_pos) in self.children(node_pos) {
+                if label == 0 {
+                    num_leaves += 1;
+                    max_depth = max_depth.max(depth);
+                } else {
+                    stack.push((child_pos, depth + 1));
+                }
+            }
+        }
+
+        DoubleArrayStats {
+            num_units: self.num_units(),
+            num_leaves,
+            max_depth,
+        }
+    }
+
+    /// Returns an iterator over positions and values of all leaves. Unlike `entries()`, it scans
+    /// units linearly without traversing nodes, so keys are not reconstructed and the order is
+    /// the order of positions.
+    pub fn leaves(&self) -> impl Iterator<Item = (UnitID, u32)> + '_ {
+        (0..self.num_units()).filter_map(move |i| {
+            let unit = self.get_unit(i)?;
+            if unit.is_leaf() {
+                Some((i, unit.value()))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Returns the number of distinct values, which is less than `len()` if some keys share the
+    /// same value. It scans leaves by `leaves()` and collects values into a set, so it allocates
+    /// memory in proportion to the number of distinct values.
+    pub fn count_distinct_values(&self) -> usize {
+        self.leaves()
+            .map(|(_, value)| value)
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// Returns the length of the longest path from the root to a leaf, which equals to the length
+    /// of the longest key. It traverses all nodes like `stats()`.
+    pub fn max_depth(&self) -> usize {
+        self.stats().max_depth
+    }
+
+    /// Returns the length of the longest key. This is an alias of `max_depth()`.
+    pub fn longest_key_len(&self) -> usize {
+        self.max_depth()
+    }
+
+    /// Returns a Graphviz DOT description of nodes reachable from the root. Edges are labeled with
+    /// transition bytes, and leaves are labeled with their values.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph {\n");
+
+        let mut stack = vec![0 as UnitID];
+        let mut remaining_steps = self.num_units(); // guarantees termination on corrupt bytes
+        while let Some(node_pos) = stack.pop() {
+            if remaining_steps == 0 {
+                break;
+            }
+            remaining_steps -= 1;
+            dot.push_str(&format!("  {};\n", node_pos));
+            for (label, child_pos) in self.children(node_pos) {
+                if label == 0 {
+                    let value = self.get_unit(child_pos).map_or(0, |unit| unit.value());
+                    let escaped = match TERM {
+                        0 => "\\\\0".to_string(),
+                        _ => escape_dot_label(TERM),
+                    };
+                    dot.push_str(&format!(
+                        "  {} [shape=box, label=\"{}\"];\n  {} -> {} [label=\"{}\"];\n",
+                        child_pos, value, node_pos, child_pos, escaped
+                    ));
+                } else {
+                    dot.push_str(&format!(
+                        "  {} -> {} [label=\"{}\"];\n",
+                        node_pos,
+                        child_pos,
+                        escape_dot_label(label ^ TERM)
+                    ));
+                    stack.push(child_pos);
+                }
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Returns labels and positions of child nodes of a non-leaf node. Labels are relabeled bytes,
+    /// and the label of a leaf is 0.
+    fn children(&self, node_pos: UnitID) -> impl Iterator<Item = (u8, UnitID)> + '_ {
+        let (is_node, offset, has_leaf) = match self.get_unit(node_pos) {
+            Some(unit) if !unit.is_leaf() => {
+                (true, unit.offset() ^ node_pos as u32, unit.has_leaf())
+            }
+            _ => (false, 0, false),
+        };
+        (0..=255u8).filter_map(move |label| {
+            if !is_node {
+                return None;
+            }
+            let child_pos = (offset ^ label as u32) as UnitID;
+            let child = self.get_unit(child_pos)?;
+            let is_child = if label == 0 {
+                has_leaf && child.is_leaf()
+            } else {
+                // offsets are unique, so a unit with the same label must be a child of the node
+                child.label() == label as u32
+            };
+            if is_child {
+                Some((label, child_pos))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Returns the unit at `index`, or `None` if `index` is out of bounds. The header of a
+    /// headered double array is not a unit.
+    #[inline]
+    pub fn unit_at(&self, index: UnitID) -> Option<Unit> {
+        if index >= self.num_units() {
+            return None;
+        }
+        self.get_unit(index)
+    }
+
+    /// Returns all units of the double array. If the `bytemuck` feature is enabled, the target is
+    /// little-endian and the bytes are aligned to 4 bytes, the bytes are borrowed as units without
+    /// decoding. Otherwise, units are decoded into a new `Vec`. Trailing bytes shorter than a unit
+    /// and the header of a headered double array are not included.
+    pub fn units(&self) -> Cow<'_, [Unit]> {
+        let bytes = &self.0[..self.num_units() * UNIT_SIZE];
+        #[cfg(all(feature = "bytemuck", target_endian = "little"))]
+        {
+            if let Ok(units) = bytemuck::try_cast_slice(bytes) {
+                return Cow::Borrowed(units);
+            }
+        }
+        Cow::Owned(
+            bytes
+                .chunks_exact(UNIT_SIZE)
+                .map(|b| Unit::from_u32(u32::from_le_bytes(b.try_into().unwrap())))
+                .collect(),
+        )
+    }
+
+    /// Returns the number of units in the double array. The header of a headered double array is
+    /// not counted.
+    pub fn num_units(&self) -> usize {
+        let len = match self.header() {
+            Some(_) => self.0.len() - header::HEADER_SIZE,
+            None => self.0.len(),
+        };
+        len / UNIT_SIZE
+    }
+
+    /// Returns the size of the double array in bytes, which equals to the serialized size
+    /// including the header of a headered double array.
+    pub fn memory_size(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Reads a unit at `index` of the bytes, which may be in the header of a headered double
+    /// array. Internal searches use it instead of `unit_at()` so as not to parse the header.
+    #[inline(always)]
+    fn get_unit(&self, index: usize) -> Option<Unit> {
+        // positions computed from corrupt bytes may be out of bounds, so they are checked against
+        // the number of units once, and the unit is read without checking the slice again
+        if index >= self.0.len() / UNIT_SIZE {
+            return None;
+        }
+        let b = unsafe {
+            self.0
+                .get_unchecked(index * UNIT_SIZE..(index + 1) * UNIT_SIZE)
+        };
+        Some(Unit::from_u32(u32::from_le_bytes(b.try_into().unwrap())))
+    }
+}
+
+/// Escapes a transition byte as a label of an edge in the DOT language.
+fn escape_dot_label(label: u8) -> String {
+    match label {
+        b'"' => "\\\"".to_string(),
+        b'\\' => "\\\\".to_string(),
+        0x20..=0x7E => (label as char).to_string(),
+        _ => format!("0x{:02X}", label),
+    }
+}
+
+impl DoubleArray<Vec<u8>> {
+    /// Creates a new `DoubleArray` from units built by `DoubleArrayBuilder::build_units()`. The
+    /// units are serialized into bytes.
+    pub fn from_units(units: Vec<Unit>) -> Self {
+        Self::from_u32(units.into_iter().map(|unit| unit.as_u32()).collect())
+    }
+
+    /// Creates a new `DoubleArray` from units built by `DoubleArrayBuilder::build_u32()`. The
+    /// units are serialized into bytes.
+    pub fn from_u32(units: Vec<u32>) -> Self {
+        let mut bytes = Vec::with_capacity(units.len() * UNIT_SIZE);
+        for unit in units {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+        Self::new(bytes)
+    }
+}
+
+impl<const TERM: u8> DoubleArray<Vec<u8>, TERM> {
+    /// Replaces the value associated with a `key` by `value` in place, and returns the old value.
+    /// Returns `Err(())` if the `key` doesn't exist or `value` can't be represented as 31 bits.
+    #[allow(clippy::result_unit_err)]
+    pub fn update_value<K>(&mut self, key: K, value: u32) -> Result<u32, ()>
+    where
+        K: AsRef<[u8]>,
+    {
+        if value >= (1 << 31) {
+            return Err(());
+        }
+        let key = key.as_ref();
+        let (node_pos, len) = self.traverse(key);
+        if len < key.len() {
+            return Err(());
+        }
+        let unit = self.get_unit(node_pos).ok_or(())?;
+        if unit.is_leaf() || !unit.has_leaf() {
+            return Err(());
+        }
+        let leaf_pos = (unit.offset() ^ node_pos as u32) as UnitID;
+        let mut leaf = self.get_unit(leaf_pos).ok_or(())?;
+        if !leaf.is_leaf() {
+            return Err(());
+        }
+
+        let old_value = leaf.value();
+        leaf.set_value(value);
+        let begin = leaf_pos * UNIT_SIZE;
+        self.0[begin..begin + UNIT_SIZE].copy_from_slice(&leaf.as_u32().to_le_bytes());
+        Ok(old_value)
+    }
+
+    /// Replaces values associated with keys of `updates` in place by `update_value()` in order,
+    /// and returns the result of each update. Failed updates don't stop the rest, and only values
+    /// of leaves are changed, so the structure of the double array is kept.
+    #[allow(clippy::result_unit_err)]
+    pub fn update_values<K>(&mut self, updates: &[(K, u32)]) -> Vec<Result<u32, ()>>
+    where
+        K: AsRef<[u8]>,
+    {
+        updates
+            .iter()
+            .map(|(key, value)| self.update_value(key, *value))
+            .collect()
+    }
+}
+
+/// An iterator that finds all values with a common prefix.
+pub struct CommonPrefixSearch<'k, 'd, T, const TERM: u8 = 0>
+where
+    T: Deref<Target = [u8]>,
+{
+    key: &'k [u8],
+    double_array: &'d DoubleArray<T, TERM>,
+    unit_id: UnitID,
+    key_pos: usize,
+}
+
+impl<T, const TERM: u8> Iterator for CommonPrefixSearch<'_, '_, T, TERM>
+where
+    T: Deref<Target = [u8]>,
+{
+    type Item = (u32, usize);
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut unit = self.double_array.get_unit(self.unit_id)?;
+        while self.key_pos < self.key.len() {
+            let c = *self.key.get(self.key_pos)? ^ TERM;
+            self.key_pos += 1;
+
+            self.unit_id = (unit.offset() ^ self.unit_id as u32 ^ c as u32) as UnitID;
+            unit = self.double_array.get_unit(self.unit_id)?;
+            if unit.label() != c as u32 {
+                return None;
+            }
+            if unit.has_leaf() {
+                let leaf_pos = unit.offset() ^ self.unit_id as u32;
+                let leaf_unit = self.double_array.get_unit(leaf_pos as UnitID)?;
+                return Some((leaf_unit.value(), self.key_pos));
+            }
+        }
+        None
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // each byte of the rest of the key can be the end of a match at most once
+        (0, Some(self.key.len() - self.key_pos))
+    }
+}
+
+/// An iterator that finds all values with a common prefix with bytes pulled from an iterator.
+pub struct CommonPrefixSearchIter<'d, T, I, const TERM: u8 = 0>
+where
+    T: Deref<Target = [u8]>,
+{
+    bytes: I,
+    double_array: &'d DoubleArray<T, TERM>,
+    unit_id: UnitID,
+    key_pos: usize,
+    finished: bool,
+}
+
+impl<T, I, const TERM: u8> CommonPrefixSearchIter<'_, T, I, TERM>
+where
+    T: Deref<Target = [u8]>,
+    I: Iterator<Item = u8>,
+{
+    #[inline(always)]
+    fn find_next(&mut self) -> Option<(u32, usize)> {
+        let mut unit = self.double_array.get_unit(self.unit_id)?;
+        loop {
+            let c = self.bytes.next()? ^ TERM;
+            self.key_pos += 1;
+
+            self.unit_id = (unit.offset() ^ self.unit_id as u32 ^ c as u32) as UnitID;
+            unit = self.double_array.get_unit(self.unit_id)?;
+            if unit.label() != c as u32 {
+                return None;
+            }
+            if unit.has_leaf() {
+                let leaf_pos = unit.offset() ^ self.unit_id as u32;
+                let leaf_unit = self.double_array.get_unit(leaf_pos as UnitID)?;
+                return Some((leaf_unit.value(), self.key_pos));
+            }
+        }
+    }
+}
+
+impl<T, I, const TERM: u8> Iterator for CommonPrefixSearchIter<'_, T, I, TERM>
+where
+    T: Deref<Target = [u8]>,
+    I: Iterator<Item = u8>,
+{
+    type Item = (u32, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let item = self.find_next();
+        self.finished = item.is_none();
+        item
+    }
+}
+
+/// An iterator that enumerates all keys and values in depth-first order.
+pub struct Entries<'d, T, const TERM: u8 = 0>
+where
+    T: Deref<Target = [u8]>,
+{
+    double_array: &'d DoubleArray<T, TERM>,
+    stack: Vec<(u8, UnitID, usize)>, // tuple of (label, node position, depth of the parent)
+    key: Vec<u8>,
+    remaining_steps: usize,
+}
+
+impl<T, const TERM: u8> Entries<'_, T, TERM>
+where
+    T: Deref<Target = [u8]>,
+{
+    fn push_children(&mut self, node_pos: UnitID, depth: usize) {
+        let stack_len = self.stack.len();
+        self.stack.extend(
+            self.double_array
+                .children(node_pos)
+                .map(|(label, child_pos)| (label, child_pos, depth)),
+        );
+        if TERM != 0 {
+            // relabeling doesn't preserve the order of bytes, but the leaf still comes first
+            self.stack[stack_len..]
+                .sort_unstable_by_key(|&(label, _, _)| (label != 0, label ^ TERM));
+        }
+        // pop children in ascending order of labels
+        self.stack[stack_len..].reverse();
+    }
+
+    /// Returns the next key and value like `next()`, but borrows the key from the internal buffer.
+    fn next_borrowed(&mut self) -> Option<(&[u8], u32)> {
+        while let Some((label, node_pos, depth)) = self.stack.pop() {
+            // each unit is visited at most once in a valid double array, so more steps than the
+            // number of units mean that the double array is corrupt and has a cycle
+            if self.remaining_steps == 0 {
+                self.stack.clear();
+                return None;
+            }
+            self.remaining_steps -= 1;
+            self.key.truncate(depth);
+            if label == 0 {
+                let unit = self.double_array.get_unit(node_pos)?;
+                return Some((&self.key, unit.value()));
+            }
+            self.key.push(label ^ TERM);
+            self.push_children(node_pos, depth + 1);
+        }
+        None
+    }
+}
+
+impl<T, const TERM: u8> Iterator for Entries<'_, T, TERM>
+where
+    T: Deref<Target = [u8]>,
+{
+    type Item = (Vec<u8>, u32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_borrowed()
+            .map(|(key, value)| (key.to_vec(), value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::DoubleArrayBuilder;
+    use crate::transducer::Transducer;
+    use crate::unit::{Unit, UNIT_SIZE};
+    use crate::{CommonPrefixSearch, DoubleArray, DoubleArrayError, Match};
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<DoubleArray<Vec<u8>>>();
+        assert_send_sync::<DoubleArray<&[u8]>>();
+    }
+
+    #[test]
+    fn test_build_search() {
+        let keyset = &[
+            ("a".as_bytes(), 0),
+            ("ab".as_bytes(), 1),
+            ("aba".as_bytes(), 2),
+            ("ac".as_bytes(), 3),
+            ("acb".as_bytes(), 4),
+            ("acc".as_bytes(), 5),
+            ("ad".as_bytes(), 6),
+            ("ba".as_bytes(), 7),
+            ("bb".as_bytes(), 8),
+            ("bc".as_bytes(), 9),
+            ("c".as_bytes(), 10),
+            ("caa".as_bytes(), 11),
+        ];
+
+        let da_bytes = DoubleArrayBuilder::build(keyset);
+        assert!(da_bytes.is_some());
+
+        let da = DoubleArray::new(da_bytes.unwrap());
+
+        for (key, value) in keyset {
+            assert_eq!(da.exact_match_search(key), Some(*value));
+        }
+        assert_eq!(da.exact_match_search("aa".as_bytes()), None);
+        assert_eq!(da.exact_match_search("abc".as_bytes()), None);
+        assert_eq!(da.exact_match_search("b".as_bytes()), None);
+        assert_eq!(da.exact_match_search("ca".as_bytes()), None);
+
+        assert_eq!(
+            da.common_prefix_search("a".as_bytes()).collect::<Vec<_>>(),
+            vec![(0, 1)]
+        );
+        assert_eq!(
+            da.common_prefix_search("aa".as_bytes()).collect::<Vec<_>>(),
+            vec![(0, 1)]
+        );
+        assert_eq!(
+            da.common_prefix_search("abbb".as_bytes())
+                .collect::<Vec<_>>(),
+            vec![(0, 1), (1, 2)]
+        );
+        assert_eq!(
+            da.common_prefix_search("abaa".as_bytes())
+                .collect::<Vec<_>>(),
+            vec![(0, 1), (1, 2), (2, 3)]
+        );
+        assert_eq!(
+            da.common_prefix_search("caa".as_bytes())
+                .collect::<Vec<_>>(),
+            vec![(10, 1), (11, 3)]
+        );
+        assert_eq!(
+            da.common_prefix_search("d".as_bytes()).collect::<Vec<_>>(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_build_search_single_key() {
+        let keyset = &[("hello", 42)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+        assert_eq!(da.validate(), Ok(()));
+
+        assert_eq!(da.exact_match_search("hello"), Some(42));
+        for key in &["", "h", "hell", "hello!", "world"] {
+            assert_eq!(da.exact_match_search(key), None);
+        }
+
+        assert_eq!(
+            da.common_prefix_search("hello, world").collect::<Vec<_>>(),
+            vec![(42, 5)]
+        );
+        assert_eq!(da.common_prefix_search("hell").count(), 0);
+        assert_eq!(
+            da.entries().collect::<Vec<_>>(),
+            vec![(b"hello".to_vec(), 42)]
+        );
+    }
+
+    #[test]
+    fn test_exact_match_search_with_len() {
+        let keyset = &[("a", 0), ("abc", 1)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        assert_eq!(da.exact_match_search_with_len("a"), Some((0, 1)));
+        assert_eq!(da.exact_match_search_with_len("abc"), Some((1, 3)));
+        assert_eq!(da.exact_match_search_with_len("ab"), None);
+        assert_eq!(
+            da.exact_match_search_with_len("abc"),
+            da.common_prefix_search("abc").last()
+        );
+    }
+
+    #[test]
+    fn test_get_or() {
+        let keyset = &[("a", 0), ("ab", 1)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+        assert_eq!(da.get_or("a", 10), 0);
+        assert_eq!(da.get_or("ab", 10), 1);
+        assert_eq!(da.get_or("abc", 10), 10);
+        assert_eq!(da.get_or("", 10), 10);
+    }
+
+    #[test]
+    fn test_value_of() {
+        let keyset = &[("a", 0), ("ab", 1)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+        assert_eq!(da.value_of("a"), 0);
+        assert_eq!(da.value_of(b"ab"), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "key not found: \"abc\"")]
+    fn test_value_of_missing_key() {
+        let keyset = &[("a", 0), ("ab", 1)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+        da.value_of("abc");
+    }
+
+    #[test]
+    fn test_exact_match_raw() {
+        let keyset = &[("a", 0), ("ab", 1), ("b", (1 << 31) - 1)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+        assert_eq!(da.exact_match_raw("a"), Some(1 << 31));
+        assert_eq!(da.exact_match_raw("ab"), Some(1 << 31 | 1));
+        assert_eq!(da.exact_match_raw("b"), Some(u32::MAX));
+        assert_eq!(da.exact_match_raw("abc"), None);
+
+        let unit = Unit::from_u32(da.exact_match_raw("ab").unwrap());
+        assert!(unit.is_leaf());
+        assert_eq!(unit.value(), 1);
+    }
+
+    #[test]
+    fn test_exact_match_search_iter() {
+        let keyset = &[("ab", 0), ("abcd", 1)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        let pieces: &[&[u8]] = &[b"ab", b"c", b"d"];
+        let key = pieces.iter().flat_map(|piece| piece.iter().copied());
+        assert_eq!(da.exact_match_search_iter(key), Some(1));
+        assert_eq!(da.exact_match_search_iter(b"ab".iter().copied()), Some(0));
+        assert_eq!(da.exact_match_search_iter(b"abc".iter().copied()), None);
+        assert_eq!(da.exact_match_search_iter(vec![b'a', 0]), None);
+
+        // the rest of bytes is not consumed after a failed transition
+        let mut bytes = b"axcd".iter().copied();
+        assert_eq!(da.exact_match_search_iter(&mut bytes), None);
+        assert_eq!(bytes.next(), Some(b'c'));
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let keyset = &[("a".as_bytes(), 0), ("ab".as_bytes(), 1)];
+        let da_bytes = DoubleArrayBuilder::build(keyset).unwrap();
+
+        let da = DoubleArray::from_bytes(da_bytes.as_slice()).unwrap();
+        assert_eq!(da.exact_match_search("ab"), Some(1));
+        for truncated in 1..=3 {
+            let len = da_bytes.len() - truncated;
+            assert_eq!(
+                DoubleArray::from_bytes(&da_bytes[..len]).err(),
+                Some(DoubleArrayError::MisalignedLength {
+                    len,
+                    remainder: UNIT_SIZE - truncated
+                })
+            );
+        }
+        assert_eq!(
+            DoubleArray::from_bytes(&da_bytes[..0]).err(),
+            Some(DoubleArrayError::InvalidLength { len: 0 })
+        );
+
+        let wide_bytes = DoubleArrayBuilder::build_wide(keyset).unwrap();
+        assert_eq!(
+            DoubleArray::from_bytes(wide_bytes).err(),
+            Some(DoubleArrayError::WideFormat)
+        );
+    }
+
+    #[test]
+    fn test_common_prefix_search_iter() {
+        let keyset = &[
+            ("a".as_bytes(), 0),
+            ("ab".as_bytes(), 1),
+            ("abc".as_bytes(), 2),
+            ("b".as_bytes(), 3),
+        ];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        for key in &["", "a", "abcd", "abd", "b", "c"] {
+            assert_eq!(
+                da.common_prefix_search_iter(key.bytes())
+                    .collect::<Vec<_>>(),
+                da.common_prefix_search(key).collect::<Vec<_>>()
+            );
+        }
+
+        // the bytes after a failed transition are never pulled
+        let mut bytes = "abxyz".bytes();
+        assert_eq!(
+            da.common_prefix_search_iter(bytes.by_ref())
+                .collect::<Vec<_>>(),
+            vec![(0, 1), (1, 2)]
+        );
+        assert_eq!(bytes.collect::<Vec<_>>(), b"yz".to_vec());
+    }
+
+    #[test]
+    fn test_common_prefix_search_size_hint() {
+        let keyset = &[("a".as_bytes(), 0), ("ab".as_bytes(), 1)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        let mut iter = da.common_prefix_search("abc");
+        assert_eq!(iter.size_hint(), (0, Some(3)));
+        iter.next();
+        assert_eq!(iter.size_hint(), (0, Some(2)));
+    }
+
+    #[test]
+    fn test_common_prefix_search_lifetimes() {
+        // the key outlives the borrow of the double array
+        const KEY: &str = "abc";
+        let da = DoubleArray::new(DoubleArrayBuilder::build(&[("a", 0), ("ab", 1)]).unwrap());
+        let iter: CommonPrefixSearch<'static, '_, Vec<u8>> = da.common_prefix_search(KEY);
+        assert_eq!(iter.collect::<Vec<_>>(), vec![(0, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn test_fold_common_prefix() {
+        let keyset = &[("a", 0), ("ab", 1), ("abc", 2), ("b", 3)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        for key in &["abcd", "abc", "ab", "b", "bc", "c", ""] {
+            let folded = da.fold_common_prefix(key, vec![], |mut acc, value, len| {
+                acc.push((value, len));
+                acc
+            });
+            assert_eq!(folded, da.common_prefix_search(key).collect::<Vec<_>>());
+        }
+        assert_eq!(
+            da.fold_common_prefix("abcd", 0, |acc, value, _| acc + value),
+            3
+        );
+        assert_eq!(
+            DoubleArray::new(vec![]).fold_common_prefix("a", 0, |acc, _, _| acc + 1),
+            0
+        );
+    }
+
+    #[test]
+    fn test_common_prefix_search_from() {
+        let keyset = &[("a", 0), ("ab", 1), ("abc", 2), ("abcd", 3), ("b", 4)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        let node = da.accept_bytes(da.root(), b"ab").unwrap();
+        assert_eq!(
+            da.common_prefix_search_from(node, b"cde")
+                .collect::<Vec<_>>(),
+            vec![(2, 1), (3, 2)]
+        );
+        assert_eq!(da.common_prefix_search_from(node, b"x").count(), 0);
+        assert_eq!(
+            da.common_prefix_search_from(da.root(), b"abc")
+                .collect::<Vec<_>>(),
+            da.common_prefix_search("abc").collect::<Vec<_>>()
+        );
+
+        let (leaf, _) = da.leaves().next().unwrap();
+        assert_eq!(da.common_prefix_search_from(leaf, b"abc").count(), 0);
+        assert_eq!(da.common_prefix_search_from(1 << 20, b"abc").count(), 0);
+    }
+
+    #[test]
+    fn test_starts_with_any() {
+        let keyset = &[("ab", 0), ("abcd", 1), ("b", 2)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        assert!(da.starts_with_any("ab"));
+        assert!(da.starts_with_any("abc"));
+        assert!(da.starts_with_any("bcd"));
+        assert!(!da.starts_with_any("a"));
+        assert!(!da.starts_with_any("c"));
+        assert!(!da.starts_with_any(""));
+
+        // the search stops at the first key, so only 2 bytes are read
+        let mut num_read = 0;
+        let bytes = b"abcd".iter().inspect(|_| num_read += 1).copied();
+        assert!(da.starts_with_any_iter(bytes));
+        assert_eq!(num_read, 2);
+        assert!(!da.starts_with_any_iter(b"cab".iter().copied()));
+    }
+
+    #[test]
+    fn test_common_prefix_matches() {
+        let keyset = &[("a", 0), ("ab", 1), ("abc", 2)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        let matches = da.common_prefix_matches("abd").collect::<Vec<_>>();
+        assert_eq!(
+            matches,
+            vec![Match { value: 0, len: 1 }, Match { value: 1, len: 2 }]
+        );
+        assert_eq!(
+            matches
+                .into_iter()
+                .map(Into::into)
+                .collect::<Vec<(u32, usize)>>(),
+            da.common_prefix_search("abd")
+                .collect::<Vec<(u32, usize)>>()
+        );
+    }
+
+    #[test]
+    fn test_common_prefix_search_str() {
+        // "\u{3042}" is encoded as [0xE3, 0x81, 0x82] and [0xE3] splits it
+        let keyset = &[
+            ("a".as_bytes(), 0),
+            (&[b'a', 0xE3][..], 2),
+            ("a\u{3042}".as_bytes(), 1),
+        ];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        assert_eq!(
+            da.common_prefix_search_str("a\u{3042}b")
+                .collect::<Vec<_>>(),
+            vec![(0, "a"), (1, "a\u{3042}")]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_greedy() {
+        let keyset = &[
+            ("東".as_bytes(), 0),
+            ("東京".as_bytes(), 1),
+            ("東京都".as_bytes(), 2),
+            ("京都".as_bytes(), 3),
+            ("に".as_bytes(), 4),
+            ("住む".as_bytes(), 5),
+        ];
+        let mut keyset = keyset.to_vec();
+        keyset.sort();
+        let da = DoubleArray::new(DoubleArrayBuilder::build(&keyset).unwrap());
+
+        let text = "東京都に住む人";
+        assert_eq!(
+            da.tokenize_greedy(text.as_bytes()).collect::<Vec<_>>(),
+            vec![(0, 9, 2), (9, 12, 4), (12, 18, 5)]
+        );
+        assert_eq!(da.tokenize_greedy(b"").count(), 0);
+    }
+
+    #[test]
+    fn test_find_overlapping() {
+        let keyset = &[("he", 0), ("hers", 1), ("his", 2), ("s", 3), ("she", 4)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        assert_eq!(
+            da.find_overlapping(b"ushers").collect::<Vec<_>>(),
+            vec![(1, 2, 3), (1, 4, 4), (2, 4, 0), (2, 6, 1), (5, 6, 3)]
+        );
+        assert_eq!(
+            da.find_overlapping(b"hishe").collect::<Vec<_>>(),
+            vec![(0, 3, 2), (2, 3, 3), (2, 5, 4), (3, 5, 0)]
+        );
+        assert_eq!(da.find_overlapping(b"xyz").count(), 0);
+        assert_eq!(da.find_overlapping(b"").count(), 0);
+    }
+
+    #[test]
+    fn test_common_prefix_search_into() {
+        let keyset = &[
+            ("a".as_bytes(), 0),
+            ("ab".as_bytes(), 1),
+            ("abc".as_bytes(), 2),
+        ];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        let mut out = [(0, 0); 4];
+        assert_eq!(da.common_prefix_search_into("abcd", &mut out), 3);
+        assert_eq!(out[..3], [(0, 1), (1, 2), (2, 3)]);
+
+        let mut out = [(0, 0); 2];
+        assert_eq!(da.common_prefix_search_into("abcd", &mut out), 2);
+        assert_eq!(out, [(0, 1), (1, 2)]);
+
+        assert_eq!(da.common_prefix_search_into("b", &mut out), 0);
+    }
+
+    #[test]
+    fn test_common_prefix_search_read() {
+        let keyset = &[
+            ("a".as_bytes(), 0),
+            ("ab".as_bytes(), 1),
+            ("b".as_bytes(), 2),
+        ];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        let mut reader: &[u8] = b"abxyz";
+        assert_eq!(
+            da.common_prefix_search_read(&mut reader).unwrap(),
+            vec![(0, 1), (1, 2)]
+        );
+        assert_eq!(reader, b"yz");
+
+        struct FailingReader;
+        impl std::io::Read for FailingReader {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::Other.into())
+            }
+        }
+        assert!(da.common_prefix_search_read(&mut FailingReader).is_err());
+    }
+
+    #[test]
+    fn test_longest_traversable_prefix_len() {
+        let keyset = &[
+            ("abc".as_bytes(), 0),
+            ("abd".as_bytes(), 1),
+            ("b".as_bytes(), 2),
+        ];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        assert_eq!(da.longest_traversable_prefix_len(""), 0);
+        assert_eq!(da.longest_traversable_prefix_len("a"), 1);
+        assert_eq!(da.longest_traversable_prefix_len("abx"), 2);
+        assert_eq!(da.longest_traversable_prefix_len("abcd"), 3);
+        assert_eq!(da.longest_traversable_prefix_len("bc"), 1);
+        assert_eq!(da.longest_traversable_prefix_len("c"), 0);
+        assert_eq!(da.longest_traversable_prefix_len("a\0"), 1);
+    }
+
+    #[test]
+    fn test_count_keys_with_prefix() {
+        let keyset = &[
+            ("a".as_bytes(), 0),
+            ("abc".as_bytes(), 1),
+            ("abd".as_bytes(), 2),
+            ("b".as_bytes(), 3),
+        ];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        assert_eq!(da.count_keys_with_prefix(""), 4);
+        assert_eq!(da.count_keys_with_prefix("a"), 3);
+        assert_eq!(da.count_keys_with_prefix("ab"), 2);
+        assert_eq!(da.count_keys_with_prefix("abc"), 1);
+        assert_eq!(da.count_keys_with_prefix("abcd"), 0);
+        assert_eq!(da.count_keys_with_prefix("c"), 0);
+    }
+
+    #[test]
+    fn test_prefix_keys() {
+        let keyset = &[
+            ("c".as_bytes(), 0),
+            ("ca".as_bytes(), 1),
+            ("caa".as_bytes(), 2),
+            ("cb".as_bytes(), 3),
+            ("d".as_bytes(), 4),
+        ];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        assert_eq!(
+            da.prefix_keys("ca").collect::<Vec<_>>(),
+            vec![b"ca".to_vec(), b"caa".to_vec()]
+        );
+        assert_eq!(
+            da.prefix_keys("c").collect::<Vec<_>>(),
+            vec![
+                b"c".to_vec(),
+                b"ca".to_vec(),
+                b"caa".to_vec(),
+                b"cb".to_vec()
+            ]
+        );
+        assert_eq!(
+            da.prefix_keys("").collect::<Vec<_>>(),
+            da.entries().map(|(key, _)| key).collect::<Vec<_>>()
+        );
+        assert_eq!(da.prefix_keys("caaa").count(), 0);
+        assert_eq!(da.prefix_keys("e").count(), 0);
+    }
+
+    #[test]
+    fn test_contains_prefix() {
+        let keyset = &[("a", 0), ("abc", 1), ("b", 2)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        assert!(da.contains_prefix(""));
+        assert!(da.contains_prefix("a"));
+        assert!(da.contains_prefix("ab"));
+        assert!(da.contains_prefix("abc"));
+        assert!(!da.contains_prefix("abcd"));
+        assert!(!da.contains_prefix("c"));
+        assert!(!da.contains_prefix("a\0"));
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let keyset = &[
+            ("a".as_bytes(), 1),
+            ("a\"".as_bytes(), 2),
+            (&[0xE3u8][..], 3),
+        ];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        let dot = da.to_dot();
+        assert!(dot.starts_with("digraph {\n  0;\n"));
+        assert!(dot.ends_with("}\n"));
+        assert_eq!(dot.matches("shape=box").count(), 3);
+        assert!(dot.contains("[label=\"a\"]"));
+        assert!(dot.contains("[label=\"\\\"\"]"));
+        assert!(dot.contains("[label=\"0xE3\"]"));
+        assert!(dot.contains("[shape=box, label=\"2\"]"));
+    }
+
+    #[test]
+    fn test_predictive_top_n() {
+        let keyset = &[
+            ("a".as_bytes(), 5),
+            ("ab".as_bytes(), 2),
+            ("abc".as_bytes(), 9),
+            ("abd".as_bytes(), 2),
+            ("ac".as_bytes(), 1),
+            ("b".as_bytes(), 0),
+        ];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        assert_eq!(
+            da.predictive_top_n("a", 3, |a, b| a.cmp(&b)),
+            vec![
+                (b"ac".to_vec(), 1),
+                (b"ab".to_vec(), 2),
+                (b"abd".to_vec(), 2)
+            ]
+        );
+        assert_eq!(
+            da.predictive_top_n("ab", 2, |a, b| b.cmp(&a)),
+            vec![(b"abc".to_vec(), 9), (b"ab".to_vec(), 2)]
+        );
+        assert_eq!(da.predictive_top_n("ab", 10, |a, b| a.cmp(&b)).len(), 3);
+        assert_eq!(da.predictive_top_n("ab", 0, |a, b| a.cmp(&b)), vec![]);
+        assert_eq!(da.predictive_top_n("c", 1, |a, b| a.cmp(&b)), vec![]);
+    }
+
+    #[test]
+    fn test_build_units() {
+        let keyset = &[("a", 0), ("ab", 1), ("b", 2)];
+        let da_bytes = DoubleArrayBuilder::build(keyset).unwrap();
+
+        let units = DoubleArrayBuilder::build_units(keyset).unwrap();
+        assert_eq!(units.len() * 4, da_bytes.len());
+        let da = DoubleArray::from_units(units);
+        assert_eq!(da.0, da_bytes);
+
+        let units = DoubleArrayBuilder::build_u32(keyset).unwrap();
+        let da = DoubleArray::from_u32(units);
+        assert_eq!(da.0, da_bytes);
+        assert_eq!(da.exact_match_search("ab"), Some(1));
+
+        assert_eq!(DoubleArrayBuilder::build_u32(&[("b", 0), ("a", 1)]), None);
+    }
+
+    #[test]
+    fn test_ordinal_of() {
+        let keys = (0..1000u32)
+            .map(|i| format!("{:x}", i.wrapping_mul(2654435761)))
+            .collect::<std::collections::BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let da = DoubleArray::new(DoubleArrayBuilder::build_ordinals(&keys).unwrap());
+
+        let mut seen = vec![false; keys.len()];
+        for (i, key) in keys.iter().enumerate() {
+            let ordinal = da.ordinal_of(key).unwrap();
+            assert_eq!(ordinal, i);
+            assert!(!seen[ordinal]);
+            seen[ordinal] = true;
+        }
+        assert!(seen.iter().all(|&seen| seen));
+        assert_eq!(da.ordinal_of("xyz"), None);
+
+        assert_eq!(DoubleArrayBuilder::build_ordinals(&["b", "a"]), None);
+        assert_eq!(DoubleArrayBuilder::build_ordinals(&["a", "a"]), None);
+    }
+
+    #[test]
+    fn test_update_value() {
+        let keyset = &[
+            ("a".as_bytes(), 0),
+            ("ab".as_bytes(), 1),
+            ("b".as_bytes(), 2),
+        ];
+        let mut da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        assert_eq!(da.update_value("ab", 10), Ok(1));
+        assert_eq!(da.exact_match_search("ab"), Some(10));
+        assert_eq!(da.exact_match_search("a"), Some(0));
+        assert_eq!(da.exact_match_search("b"), Some(2));
+
+        assert_eq!(da.update_value("abc", 10), Err(()));
+        assert_eq!(da.update_value("", 10), Err(()));
+        assert_eq!(da.update_value("b", 1 << 31), Err(()));
+        assert_eq!(da.exact_match_search("b"), Some(2));
+    }
+
+    #[test]
+    fn test_update_values() {
+        let keyset = &[("a", 0), ("ab", 1), ("b", 2)];
+        let mut da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+        let units = da.num_units();
+
+        let updates = &[("a", 10), ("abc", 11), ("b", 1 << 31), ("b", 12), ("b", 13)];
+        assert_eq!(
+            da.update_values(updates),
+            vec![Ok(0), Err(()), Err(()), Ok(2), Ok(12)]
+        );
+        assert_eq!(da.num_units(), units);
+        assert_eq!(
+            da.entries().collect::<Vec<_>>(),
+            vec![
+                (b"a".to_vec(), 10),
+                (b"ab".to_vec(), 1),
+                (b"b".to_vec(), 13)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unit_at() {
+        let keyset = &[("a".as_bytes(), 0), ("ab".as_bytes(), 1)];
+
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+        assert_eq!(da.num_units(), 256);
+        assert!(da.unit_at(0).is_some());
+        assert!(da.unit_at(255).is_some());
+        assert!(da.unit_at(256).is_none());
+        assert!(da.unit_at(usize::MAX).is_none());
+        assert!(da.unit_at(usize::MAX / UNIT_SIZE + 1).is_none());
+        assert!(da.get_unit(usize::MAX / UNIT_SIZE).is_none());
+    }
+
+    #[test]
+    fn test_units() {
+        let keyset = &[("a", 0), ("ab", 1), ("b", 2)];
+        let da_bytes = DoubleArrayBuilder::build(keyset).unwrap();
+        let da = DoubleArray::new(da_bytes.as_slice());
+        let units = da.units();
+        assert_eq!(units.len(), da.num_units());
+        for (i, unit) in units.iter().enumerate() {
+            assert_eq!(Some(*unit), da.unit_at(i));
+        }
+
+        // misaligned bytes are decoded
+        let mut bytes = vec![0u8];
+        bytes.extend_from_slice(&da_bytes);
+        let da = DoubleArray::new(&bytes[1..]);
+        assert_eq!(da.units(), units);
+    }
+
+    #[test]
+    fn test_entries() {
+        let keyset = &[
+            ("a".as_bytes(), 0),
+            ("ab".as_bytes(), 1),
+            ("abcd".as_bytes(), 2),
+            ("b".as_bytes(), 3),
+            ("ba".as_bytes(), 4),
+        ];
+
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+        assert_eq!(
+            da.entries().collect::<Vec<_>>(),
+            keyset
+                .iter()
+                .map(|(key, value)| (key.to_vec(), *value))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_keys_values_iter() {
+        let keyset = &[("a", 4), ("ab", 3), ("b", 2), ("ba", 0)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        assert_eq!(
+            da.keys().collect::<Vec<_>>(),
+            vec![b"a".to_vec(), b"ab".to_vec(), b"b".to_vec(), b"ba".to_vec()]
+        );
+        assert_eq!(da.values().collect::<Vec<_>>(), vec![4, 3, 2, 0]);
+        assert_eq!(
+            da.iter().collect::<Vec<_>>(),
+            da.keys().zip(da.values()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_reconstruct_keyset() {
+        let keyset = &[
+            ("a".as_bytes(), 0),
+            ("ab".as_bytes(), 1),
+            ("abcd".as_bytes(), 2),
+            ("b".as_bytes(), 3),
+            ("ba".as_bytes(), 4),
+            ("\u{3042}".as_bytes(), 5),
+        ];
+
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+        let reconstructed = da.reconstruct_keyset();
+        assert_eq!(
+            reconstructed,
+            keyset
+                .iter()
+                .map(|(key, value)| (key.to_vec(), *value))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            DoubleArrayBuilder::build(&reconstructed),
+            DoubleArrayBuilder::build(keyset)
+        );
+    }
+
+    #[test]
+    fn test_leaves() {
+        let keyset = &[("a", 0), ("ab", 1), ("b", 2)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        let leaves = da.leaves().collect::<Vec<_>>();
+        assert_eq!(leaves.len(), 3);
+        let mut values = leaves.iter().map(|&(_, value)| value).collect::<Vec<_>>();
+        values.sort_unstable();
+        assert_eq!(values, vec![0, 1, 2]);
+        for (unit_id, value) in leaves {
+            assert_eq!(da.unit_at(unit_id).unwrap().value(), value);
+        }
+
+        let da = DoubleArray::new(DoubleArrayBuilder::build_with_header(keyset).unwrap());
+        assert_eq!(da.leaves().count(), 3);
+    }
+
+    #[test]
+    fn test_count_distinct_values() {
+        let keyset = &[("a", 1), ("ab", 2), ("abc", 1), ("b", 3), ("bc", 2)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+        assert_eq!(da.len(), 5);
+        assert_eq!(da.count_distinct_values(), 3);
+
+        let da = DoubleArray::new(DoubleArrayBuilder::build_with_header(keyset).unwrap());
+        assert_eq!(da.count_distinct_values(), 3);
+    }
+
+    #[test]
+    fn test_for_each_entry() {
+        let keyset = &[
+            ("a".as_bytes(), 0),
+            ("ab".as_bytes(), 1),
+            ("abcd".as_bytes(), 2),
+            ("b".as_bytes(), 3),
+            ("ba".as_bytes(), 4),
+        ];
+
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+        let mut entries = vec![];
+        da.for_each_entry(|key, value| entries.push((key.to_vec(), value)));
+        assert_eq!(entries, da.entries().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_stats() {
+        let keyset = &[
+            ("a".as_bytes(), 0),
+            ("ab".as_bytes(), 1),
+            ("abcd".as_bytes(), 2),
+            ("b".as_bytes(), 3),
+        ];
+
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+        let stats = da.stats();
+        assert_eq!(stats.num_units, da.0.len() / 4);
+        assert_eq!(stats.num_leaves, 4);
+        assert_eq!(stats.max_depth, 4);
+        assert_eq!(da.max_depth(), 4);
+        assert_eq!(da.longest_key_len(), 4);
+        assert_eq!(da.memory_size(), da.0.len());
+    }
+
+    #[test]
+    fn test_exact_match_search_corner_case() {
+        // A corner case of `exact_match_search()`.
+        // See https://github.com/takuyaa/yada/pull/28 for more details.
+        let keyset = &[
+            ("a".as_bytes(), 97),
+            ("ab".as_bytes(), 1),
+            ("de".as_bytes(), 2),
+        ];
+
+        let da_bytes = DoubleArrayBuilder::build(keyset);
+        assert!(da_bytes.is_some());
+
+        let da = DoubleArray::new(da_bytes.unwrap());
+
+        for (key, value) in keyset {
+            assert_eq!(da.exact_match_search(key), Some(*value));
         }
         assert_eq!(da.exact_match_search("dasss"), None);
     }
 
+    #[test]
+    fn test_exact_match_search_null_character() {
+        let keyset = &[("a".as_bytes(), 0), ("ab".as_bytes(), 1)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+
+        assert_eq!(da.exact_match_search("a\0b"), None);
+        assert_eq!(da.exact_match_search("a\0"), None);
+        assert_eq!(da.exact_match_search("\0"), None);
+    }
+
+    #[test]
+    fn test_search_with_terminator() {
+        let keyset: &[(&[u8], u32)] = &[
+            (b"\0", 0),
+            (b"a", 1),
+            (b"a\0", 2),
+            (b"a\0b", 3),
+            (b"a\xFE", 4),
+            (b"b", 5),
+        ];
+        let da_bytes = DoubleArrayBuilder::builder()
+            .terminator(0xFF)
+            .build(keyset)
+            .unwrap();
+        let da = DoubleArray::new(da_bytes).with_terminator::<0xFF>();
+        assert_eq!(da.terminator(), 0xFF);
+        assert_eq!(da.validate(), Ok(()));
+        assert_eq!(da.len(), keyset.len());
+
+        for (key, value) in keyset {
+            assert_eq!(da.exact_match_search(key), Some(*value));
+        }
+        for key in &[&b""[..], b"\0\0", b"a\0\0", b"a\xFF", b"\xFF", b"c"] {
+            assert_eq!(da.exact_match_search(key), None);
+        }
+        assert_eq!(
+            da.common_prefix_search(b"a\0bc").collect::<Vec<_>>(),
+            vec![(1, 1), (2, 2), (3, 3)]
+        );
+        assert_eq!(
+            da.common_prefix_search_iter(b"a\0bc".iter().copied())
+                .collect::<Vec<_>>(),
+            vec![(1, 1), (2, 2), (3, 3)]
+        );
+        assert_eq!(
+            da.fold_common_prefix(b"a\xFFb", vec![], |mut acc, value, len| {
+                acc.push((value, len));
+                acc
+            }),
+            vec![(1, 1)]
+        );
+        assert_eq!(da.count_keys_with_prefix(b"a\0"), 2);
+        assert!(!da.contains_prefix(b"a\xFF"));
+
+        // keys are enumerated in the byte order of the original keys
+        assert_eq!(
+            da.entries().collect::<Vec<_>>(),
+            keyset
+                .iter()
+                .map(|(key, value)| (key.to_vec(), *value))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            da.prefix_keys(b"a\0").collect::<Vec<_>>(),
+            vec![b"a\0".to_vec(), b"a\0b".to_vec()]
+        );
+
+        // the terminator labels edges to leaves, and NULL labels an ordinary edge
+        let dot = da.to_dot();
+        assert!(dot.contains("[label=\"0xFF\"]"));
+        assert!(dot.contains("[label=\"0x00\"]"));
+
+        let mut da = da;
+        assert_eq!(da.update_value(b"a\0", 6), Ok(2));
+        assert_eq!(da.exact_match_search(b"a\0"), Some(6));
+    }
+
+    #[test]
+    fn test_exact_match_search_corrupt_bytes() {
+        // the root is a leaf
+        let da = DoubleArray::new(0x8000_0000u32.to_le_bytes().to_vec());
+        assert_eq!(da.exact_match_search("a"), None);
+
+        // the root has a leaf at 1, but the unit at 1 is not a leaf
+        let root = (1u32 << 10) | (1 << 8);
+        let mut bytes = root.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        let da = DoubleArray::new(bytes);
+        assert_eq!(da.exact_match_search(""), None);
+    }
+
+    #[test]
+    fn test_corrupt_bytes_with_cycle() {
+        // the child of the root by 'a' is the root itself
+        let root = (b'a' as u32) << 10 | b'a' as u32;
+        let da = DoubleArray::new(root.to_le_bytes().to_vec());
+        assert_eq!(da.entries().count(), 0);
+        assert_eq!(da.stats().num_leaves, 0);
+        assert_eq!(da.max_depth(), 0);
+        assert_eq!(da.count_keys_with_prefix("aaa"), 0);
+        assert!(da.predictive_top_n("a", 1, |a, b| a.cmp(&b)).is_empty());
+        assert!(!da.to_dot().is_empty());
+        assert_eq!(da.common_prefix_search("aaaa").count(), 0);
+    }
+
+    #[test]
+    fn test_random_bytes() {
+        // searches on random bytes must not panic or hang
+        let mut seed = 12345u32;
+        let mut rand = || {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 16) as u8
+        };
+        for len in 1..200 {
+            let bytes = (0..len * UNIT_SIZE).map(|_| rand()).collect::<Vec<_>>();
+            let key = (0..8).map(|_| rand()).collect::<Vec<_>>();
+            let da = DoubleArray::new(bytes);
+            let _ = da.exact_match_search(&key);
+            let _ = da.common_prefix_search(&key).count();
+            let _ = da.longest_traversable_prefix_len(&key);
+            let _ = da.count_keys_with_prefix(&key[..1]);
+            let _ = da.predictive_top_n(&key[..1], 3, |a, b| a.cmp(&b));
+            let _ = da.entries().count();
+            da.for_each_entry(|_, _| {});
+            let _ = da.stats();
+            let _ = da.to_dot();
+        }
+    }
+
     #[test]
     fn test_clone_and_search() {
         let keyset = &[
@@ -247,7 +2101,7 @@ mod tests {
         let da = da_orig.clone();
 
         for (key, value) in keyset {
-            assert_eq!(da.exact_match_search(key), Some(*value as u32));
+            assert_eq!(da.exact_match_search(key), Some(*value));
         }
         assert_eq!(da.exact_match_search("aa".as_bytes()), None);
         assert_eq!(da.exact_match_search("abc".as_bytes()), None);
@@ -0,0 +1,221 @@
+use crate::builder::DoubleArrayBuilder;
+use crate::DoubleArray;
+use std::cmp::Ordering;
+use std::ops::Deref;
+
+/// A strategy to resolve values of keys contained in both double arrays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Keeps the value of the first double array.
+    KeepA,
+    /// Keeps the value of the second double array.
+    KeepB,
+    /// Keeps the larger value.
+    Max,
+    /// Keeps the smaller value.
+    Min,
+}
+
+impl MergeStrategy {
+    fn resolve(&self, a: u32, b: u32) -> u32 {
+        match self {
+            MergeStrategy::KeepA => a,
+            MergeStrategy::KeepB => b,
+            MergeStrategy::Max => a.max(b),
+            MergeStrategy::Min => a.min(b),
+        }
+    }
+}
+
+/// Differences of keys and values between two double arrays. Each list is sorted by keys.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrieDiff {
+    /// Keys and values only in the new double array.
+    pub added: Vec<(Vec<u8>, u32)>,
+    /// Keys and values only in the old double array.
+    pub removed: Vec<(Vec<u8>, u32)>,
+    /// Keys in both double arrays with different values, as tuples of (key, old value, new value).
+    pub changed: Vec<(Vec<u8>, u32, u32)>,
+}
+
+impl<T> DoubleArray<T>
+where
+    T: Deref<Target = [u8]>,
+{
+    /// Builds a double array which contains all keys of `a` and `b`, and returns it when build
+    /// finished successfully. Otherwise, returns `None`.
+    /// Values of keys contained in both are resolved by `on_conflict`.
+    pub fn merge<U>(a: &Self, b: &DoubleArray<U>, on_conflict: MergeStrategy) -> Option<Vec<u8>>
+    where
+        U: Deref<Target = [u8]>,
+    {
+        // both entries are sorted, so merge them like a merge sort
+        let mut entries_a = a.entries().peekable();
+        let mut entries_b = b.entries().peekable();
+        let mut keyset = vec![];
+        loop {
+            let ordering = match (entries_a.peek(), entries_b.peek()) {
+                (Some((key_a, _)), Some((key_b, _))) => key_a.cmp(key_b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => break,
+            };
+            let entry = match ordering {
+                Ordering::Less => entries_a.next()?,
+                Ordering::Greater => entries_b.next()?,
+                Ordering::Equal => {
+                    let (key, value_a) = entries_a.next()?;
+                    let (_, value_b) = entries_b.next()?;
+                    (key, on_conflict.resolve(value_a, value_b))
+                }
+            };
+            keyset.push(entry);
+        }
+
+        DoubleArrayBuilder::build(&keyset)
+    }
+
+    /// Returns keys which are added, removed, or whose values are changed from `old` to `new`.
+    pub fn diff<U>(old: &Self, new: &DoubleArray<U>) -> TrieDiff
+    where
+        U: Deref<Target = [u8]>,
+    {
+        // both entries are sorted, so merge them like a merge sort
+        let mut entries_old = old.entries().peekable();
+        let mut entries_new = new.entries().peekable();
+        let mut diff = TrieDiff::default();
+        loop {
+            let ordering = match (entries_old.peek(), entries_new.peek()) {
+                (Some((key_old, _)), Some((key_new, _))) => key_old.cmp(key_new),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => break,
+            };
+            match ordering {
+                Ordering::Less => diff.removed.extend(entries_old.next()),
+                Ordering::Greater => diff.added.extend(entries_new.next()),
+                Ordering::Equal => {
+                    if let (Some((key, value_old)), Some((_, value_new))) =
+                        (entries_old.next(), entries_new.next())
+                    {
+                        if value_old != value_new {
+                            diff.changed.push((key, value_old, value_new));
+                        }
+                    }
+                }
+            }
+        }
+        diff
+    }
+
+    /// Returns keys contained in both `a` and `b` in byte order. Entries of the smaller double
+    /// array are enumerated and searched in the other one. Values are not compared.
+    pub fn intersect_keys<U>(a: &Self, b: &DoubleArray<U>) -> Vec<Vec<u8>>
+    where
+        U: Deref<Target = [u8]>,
+    {
+        if a.num_units() <= b.num_units() {
+            a.entries()
+                .map(|(key, _)| key)
+                .filter(|key| b.exact_match_search(key).is_some())
+                .collect()
+        } else {
+            b.entries()
+                .map(|(key, _)| key)
+                .filter(|key| a.exact_match_search(key).is_some())
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::DoubleArrayBuilder;
+    use crate::merge::{MergeStrategy, TrieDiff};
+    use crate::DoubleArray;
+
+    #[test]
+    fn test_merge() {
+        let da_a =
+            DoubleArray::new(DoubleArrayBuilder::build(&[("a", 1), ("ab", 2), ("b", 3)]).unwrap());
+        let da_b = DoubleArray::new(
+            DoubleArrayBuilder::build(&[("ab", 5), ("abc", 6), ("c", 7)]).unwrap(),
+        );
+
+        let strategies = [
+            (MergeStrategy::KeepA, 2),
+            (MergeStrategy::KeepB, 5),
+            (MergeStrategy::Max, 5),
+            (MergeStrategy::Min, 2),
+        ];
+        for (strategy, value) in strategies.iter() {
+            let da = DoubleArray::new(DoubleArray::merge(&da_a, &da_b, *strategy).unwrap());
+            assert_eq!(
+                da.entries().collect::<Vec<_>>(),
+                vec![
+                    (b"a".to_vec(), 1),
+                    (b"ab".to_vec(), *value),
+                    (b"abc".to_vec(), 6),
+                    (b"b".to_vec(), 3),
+                    (b"c".to_vec(), 7),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_merge_disjoint() {
+        let da_a = DoubleArray::new(DoubleArrayBuilder::build(&[("x", 1), ("y", 2)]).unwrap());
+        let da_b = DoubleArray::new(DoubleArrayBuilder::build(&[("a", 3), ("z", 4)]).unwrap());
+
+        let da = DoubleArray::new(DoubleArray::merge(&da_a, &da_b, MergeStrategy::KeepA).unwrap());
+        assert_eq!(
+            da.entries().collect::<Vec<_>>(),
+            vec![
+                (b"a".to_vec(), 3),
+                (b"x".to_vec(), 1),
+                (b"y".to_vec(), 2),
+                (b"z".to_vec(), 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_intersect_keys() {
+        let da_a =
+            DoubleArray::new(DoubleArrayBuilder::build(&[("a", 1), ("ab", 2), ("b", 3)]).unwrap());
+        let da_b = DoubleArray::new(
+            DoubleArrayBuilder::build(&[("ab", 5), ("abc", 6), ("b", 7)]).unwrap(),
+        );
+        assert_eq!(
+            DoubleArray::intersect_keys(&da_a, &da_b),
+            vec![b"ab".to_vec(), b"b".to_vec()]
+        );
+        assert_eq!(
+            DoubleArray::intersect_keys(&da_b, &da_a),
+            vec![b"ab".to_vec(), b"b".to_vec()]
+        );
+
+        let da_c = DoubleArray::new(DoubleArrayBuilder::build(&[("x", 1), ("y", 2)]).unwrap());
+        assert!(DoubleArray::intersect_keys(&da_a, &da_c).is_empty());
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = DoubleArray::new(
+            DoubleArrayBuilder::build(&[("a", 1), ("ab", 2), ("b", 3), ("c", 4)]).unwrap(),
+        );
+        let new = DoubleArray::new(
+            DoubleArrayBuilder::build(&[("ab", 5), ("abc", 6), ("b", 3), ("d", 7)]).unwrap(),
+        );
+        assert_eq!(
+            DoubleArray::diff(&old, &new),
+            TrieDiff {
+                added: vec![(b"abc".to_vec(), 6), (b"d".to_vec(), 7)],
+                removed: vec![(b"a".to_vec(), 1), (b"c".to_vec(), 4)],
+                changed: vec![(b"ab".to_vec(), 2, 5)],
+            }
+        );
+        assert_eq!(DoubleArray::diff(&old, &old), TrieDiff::default());
+    }
+}
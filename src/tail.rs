@@ -0,0 +1,215 @@
+//! A build mode which stores unique suffixes of keys in a separate tail array.
+//!
+//! Keys with long unique suffixes produce chains of nodes with a single child, and each byte of
+//! the chains takes a unit. In this mode, each key is cut at the shortest prefix which
+//! distinguishes it from the adjacent keys, and only the prefix is stored in the double array.
+//! The rest of the key is stored in the tail array with the value, and the double array stores
+//! the position of the entry in the tail instead of the value. An entry of the tail is:
+//!
+//! ```text
+//!   0..4     VALUE      the value as a little-endian u32
+//!   4..      SUFFIX     the rest of the key terminated by NULL ('\0')
+//! ```
+//!
+//! Searches traverse the double array by the prefix, and then compare the rest of the input with
+//! the suffix in the tail.
+
+use crate::builder::DoubleArrayBuilder;
+use crate::DoubleArray;
+use std::convert::TryInto;
+use std::ops::Deref;
+
+/// A double array trie whose suffixes of keys are stored in a tail array, which is built by
+/// `DoubleArrayBuilder::build_with_tail()`.
+#[derive(Clone)]
+pub struct DoubleArrayWithTail<T, U>
+where
+    T: Deref<Target = [u8]>,
+    U: Deref<Target = [u8]>,
+{
+    pub double_array: DoubleArray<T>,
+    pub tail: U,
+}
+
+impl<T, U> DoubleArrayWithTail<T, U>
+where
+    T: Deref<Target = [u8]>,
+    U: Deref<Target = [u8]>,
+{
+    /// Creates a new `DoubleArrayWithTail` with a byte slice and a tail built by
+    /// `DoubleArrayBuilder::build_with_tail()`.
+    pub fn new(bytes: T, tail: U) -> Self {
+        Self {
+            double_array: DoubleArray::new(bytes),
+            tail,
+        }
+    }
+
+    /// Finds a value associated with a `key`.
+    pub fn exact_match_search<K>(&self, key: K) -> Option<u32>
+    where
+        K: AsRef<[u8]>,
+    {
+        let key = key.as_ref();
+        // at most one of prefixes in the double array is followed by the rest of the key
+        self.double_array
+            .common_prefix_search(key)
+            .find_map(|(pos, len)| {
+                let (value, suffix) = self.tail_entry(pos)?;
+                if suffix == &key[len..] {
+                    Some(value)
+                } else {
+                    None
+                }
+            })
+    }
+
+    /// Finds all values and it's key length which have a common prefix with a `key`.
+    pub fn common_prefix_search<'b, K>(
+        &'b self,
+        key: &'b K,
+    ) -> impl Iterator<Item = (u32, usize)> + 'b
+    where
+        K: AsRef<[u8]>,
+        K: ?Sized,
+    {
+        let key = key.as_ref();
+        self.double_array
+            .common_prefix_search(key)
+            .filter_map(move |(pos, len)| {
+                let (value, suffix) = self.tail_entry(pos)?;
+                if key[len..].starts_with(suffix) {
+                    Some((value, len + suffix.len()))
+                } else {
+                    None
+                }
+            })
+    }
+
+    /// Returns the value and the suffix of the tail entry at `pos`, or `None` if the entry is
+    /// out of bounds or not terminated.
+    fn tail_entry(&self, pos: u32) -> Option<(u32, &[u8])> {
+        let entry = self.tail.get(pos as usize..)?;
+        let value = u32::from_le_bytes(entry.get(..4)?.try_into().unwrap());
+        let suffix = &entry[4..];
+        let len = suffix.iter().position(|&b| b == 0)?;
+        Some((value, &suffix[..len]))
+    }
+}
+
+impl DoubleArrayBuilder {
+    /// Builds a double-array trie which stores the shortest distinguishing prefixes of keys, and
+    /// returns it with a tail array which stores the rest of the keys and the values. Otherwise,
+    /// returns `None`. Values aren't limited to 31 bits, because they are stored in the tail.
+    /// Use `DoubleArrayWithTail` to search them.
+    /// The `keyset` must be sorted.
+    pub fn build_with_tail<T>(keyset: &[(T, u32)]) -> Option<(Vec<u8>, Vec<u8>)>
+    where
+        T: AsRef<[u8]>,
+    {
+        let lcps = keyset
+            .windows(2)
+            .map(|pair| common_prefix_len(pair[0].0.as_ref(), pair[1].0.as_ref()))
+            .collect::<Vec<_>>();
+
+        let mut tail = vec![];
+        let mut prefixes = Vec::with_capacity(keyset.len());
+        for (i, (key, value)) in keyset.iter().enumerate() {
+            let key = key.as_ref();
+            if key.contains(&0) {
+                return None;
+            }
+            // the prefix has to contain the first byte which differs from the adjacent keys
+            let prev = if i > 0 { lcps[i - 1] } else { 0 };
+            let next = lcps.get(i).copied().unwrap_or(0);
+            let prefix_len = (prev.max(next) + 1).min(key.len());
+
+            if tail.len() >= (1 << 31) {
+                return None;
+            }
+            prefixes.push((&key[..prefix_len], tail.len() as u32));
+            tail.extend_from_slice(&value.to_le_bytes());
+            tail.extend_from_slice(&key[prefix_len..]);
+            tail.push(0);
+        }
+
+        let da_bytes = Self::build(&prefixes)?;
+        Some((da_bytes, tail))
+    }
+}
+
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::DoubleArrayBuilder;
+    use crate::tail::DoubleArrayWithTail;
+    use crate::DoubleArray;
+
+    #[test]
+    fn test_build_search_with_tail() {
+        let keyset = &[
+            ("a", 0),
+            ("ab", 1),
+            ("abcdefg", 2),
+            ("abcxyz", 3),
+            ("b", 4),
+            ("bcdefghij", u32::MAX),
+        ];
+        let (da_bytes, tail) = DoubleArrayBuilder::build_with_tail(keyset).unwrap();
+        let da = DoubleArrayWithTail::new(da_bytes, tail);
+
+        for (key, value) in keyset {
+            assert_eq!(da.exact_match_search(key), Some(*value));
+        }
+        for key in &[
+            "",
+            "abc",
+            "abcdef",
+            "abcdefgh",
+            "abcxy",
+            "bc",
+            "bcdefghix",
+            "c",
+        ] {
+            assert_eq!(da.exact_match_search(key), None);
+        }
+
+        let plain = DoubleArray::new(DoubleArrayBuilder::build(&keyset[..5]).unwrap());
+        for key in &["abcdefgh", "abcxyz", "abcx", "bcdefghij", "bcd", "c", ""] {
+            assert_eq!(
+                da.common_prefix_search(key)
+                    .filter(|&(value, _)| value != u32::MAX)
+                    .collect::<Vec<_>>(),
+                plain.common_prefix_search(key).collect::<Vec<_>>()
+            );
+        }
+        assert_eq!(
+            da.common_prefix_search("bcdefghijk").collect::<Vec<_>>(),
+            vec![(4, 1), (u32::MAX, 9)]
+        );
+
+        assert_eq!(DoubleArrayBuilder::build_with_tail(&[("a\0", 0)]), None);
+        assert_eq!(
+            DoubleArrayBuilder::build_with_tail(&[("b", 0), ("a", 1)]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_build_with_tail_size() {
+        let keyset = (0..1000u32)
+            .map(|i| (format!("{:04}/a/long/unique/suffix/{}", i, i), i))
+            .collect::<Vec<_>>();
+        let da_bytes = DoubleArrayBuilder::build(&keyset).unwrap();
+        let (tail_da_bytes, tail) = DoubleArrayBuilder::build_with_tail(&keyset).unwrap();
+        assert!((tail_da_bytes.len() + tail.len()) * 2 < da_bytes.len());
+
+        let da = DoubleArrayWithTail::new(tail_da_bytes, tail);
+        for (key, value) in keyset.iter() {
+            assert_eq!(da.exact_match_search(key), Some(*value));
+        }
+    }
+}
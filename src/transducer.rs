@@ -0,0 +1,142 @@
+//! A trait which abstracts transitions of tries, so that search algorithms can be written once
+//! for different implementations.
+
+use crate::unit::UnitID;
+use crate::wide::DoubleArray64;
+use crate::DoubleArray;
+use std::ops::Deref;
+
+/// A deterministic transducer which maps byte strings to values, such as a double array trie.
+pub trait Transducer {
+    /// Returns the initial node.
+    fn root(&self) -> UnitID;
+
+    /// Returns the node reached from a `node` by a `byte`, or `None` if there is no transition.
+    fn accept(&self, node: UnitID, byte: u8) -> Option<UnitID>;
+
+    /// Returns the value if the string which reaches a `node` is a key. Otherwise, returns `None`.
+    fn value(&self, node: UnitID) -> Option<u32>;
+
+    /// Returns the node reached from a `node` by all `bytes`, or `None` if any transition fails.
+    fn accept_bytes(&self, node: UnitID, bytes: &[u8]) -> Option<UnitID> {
+        bytes
+            .iter()
+            .try_fold(node, |node, &byte| self.accept(node, byte))
+    }
+}
+
+impl<T> Transducer for DoubleArray<T>
+where
+    T: Deref<Target = [u8]>,
+{
+    fn root(&self) -> UnitID {
+        0
+    }
+
+    fn accept(&self, node: UnitID, byte: u8) -> Option<UnitID> {
+        let unit = self.get_unit(node)?;
+        if byte == 0 || unit.is_leaf() {
+            return None;
+        }
+        let child_pos = (unit.offset() ^ node as u32 ^ byte as u32) as UnitID;
+        let child = self.get_unit(child_pos)?;
+        if child.label() != byte as u32 {
+            return None;
+        }
+        Some(child_pos)
+    }
+
+    fn value(&self, node: UnitID) -> Option<u32> {
+        let unit = self.get_unit(node)?;
+        if unit.is_leaf() || !unit.has_leaf() {
+            return None;
+        }
+        let leaf = self.get_unit((unit.offset() ^ node as u32) as UnitID)?;
+        if !leaf.is_leaf() {
+            return None;
+        }
+        Some(leaf.value())
+    }
+}
+
+impl<T> Transducer for DoubleArray64<T>
+where
+    T: Deref<Target = [u8]>,
+{
+    fn root(&self) -> UnitID {
+        0
+    }
+
+    fn accept(&self, node: UnitID, byte: u8) -> Option<UnitID> {
+        let unit = self.unit_at(node)?;
+        if byte == 0 || unit.is_leaf() {
+            return None;
+        }
+        let child_pos = (unit.offset() ^ node as u64 ^ byte as u64) as UnitID;
+        let child = self.unit_at(child_pos)?;
+        if child.label() != byte as u64 {
+            return None;
+        }
+        Some(child_pos)
+    }
+
+    fn value(&self, node: UnitID) -> Option<u32> {
+        let unit = self.unit_at(node)?;
+        if unit.is_leaf() || !unit.has_leaf() {
+            return None;
+        }
+        let leaf = self.unit_at((unit.offset() ^ node as u64) as UnitID)?;
+        if !leaf.is_leaf() {
+            return None;
+        }
+        Some(leaf.value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::DoubleArrayBuilder;
+    use crate::transducer::Transducer;
+    use crate::wide::DoubleArray64;
+    use crate::DoubleArray;
+
+    // a search written once against the trait
+    fn prefix_values<A: Transducer>(automaton: &A, key: &[u8]) -> Vec<(u32, usize)> {
+        let mut node = automaton.root();
+        let mut values = vec![];
+        for (i, &byte) in key.iter().enumerate() {
+            node = match automaton.accept(node, byte) {
+                Some(node) => node,
+                None => break,
+            };
+            if let Some(value) = automaton.value(node) {
+                values.push((value, i + 1));
+            }
+        }
+        values
+    }
+
+    #[test]
+    fn test_transducer() {
+        let keyset = &[("a", 0), ("ab", 1), ("abc", 2), ("b", 3)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+        let da64 =
+            DoubleArray64::from_bytes(DoubleArrayBuilder::build_wide(keyset).unwrap()).unwrap();
+
+        for key in &["abcd", "ab", "b", "c", ""] {
+            let expected = da.common_prefix_search(key).collect::<Vec<_>>();
+            assert_eq!(prefix_values(&da, key.as_bytes()), expected);
+            assert_eq!(prefix_values(&da64, key.as_bytes()), expected);
+        }
+
+        let node = da.accept_bytes(da.root(), b"ab").unwrap();
+        assert_eq!(da.value(node), Some(1));
+        assert_eq!(
+            da.accept_bytes(node, b"c").and_then(|n| da.value(n)),
+            Some(2)
+        );
+        assert_eq!(da.accept_bytes(da.root(), b"ac"), None);
+        assert_eq!(da.accept(da.root(), 0), None);
+        assert_eq!(da.value(da.root()), None);
+    }
+}
@@ -4,10 +4,51 @@ pub type UnitID = usize;
 /// The size of `Unit` (4).
 pub const UNIT_SIZE: usize = std::mem::size_of::<u32>();
 
+/// An error that indicates a value can't be represented as a 31 bits unsigned integer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValueOutOfRange {
+    pub value: u32,
+}
+
+impl std::fmt::Display for ValueOutOfRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "value {} can't be represented as 31 bits", self.value)
+    }
+}
+
+impl std::error::Error for ValueOutOfRange {}
+
+/// An error that indicates an offset can't be stored in a unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OffsetError {
+    /// The offset can't be represented as a 29 bits unsigned integer.
+    TooLarge,
+    /// The offset is greater than 21 bits, but its lower 8 bits are not 0.
+    LowBitsSet,
+}
+
+impl std::fmt::Display for OffsetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            OffsetError::TooLarge => write!(f, "offset can't be represented as 29 bits"),
+            OffsetError::LowBitsSet => write!(f, "lower 8 bits of extended offset should be 0"),
+        }
+    }
+}
+
+impl std::error::Error for OffsetError {}
+
 /// An unit represents an element in a double-array.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Default, PartialEq, Eq, Hash)]
+#[repr(transparent)]
 pub struct Unit(u32);
 
+// Any bit pattern is a valid unit, because a unit is a transparent wrapper of `u32`.
+#[cfg(feature = "bytemuck")]
+unsafe impl bytemuck::Zeroable for Unit {}
+#[cfg(feature = "bytemuck")]
+unsafe impl bytemuck::Pod for Unit {}
+
 /// Unit represents one node of a double array trie. The bit width of each node is 32-bits.
 ///
 /// The bit layout of a non-leaf node:
@@ -96,15 +137,29 @@ impl Unit {
 
         if offset < (1u32 << 21) {
             // don't extend offset
-            self.0 = offset << 10 | (self.0 << 23 as u32) >> 23;
+            self.0 = offset << 10 | (self.0 << 23) >> 23;
         } else {
             // extend offset
             assert_eq!((offset << 2) & (1 << 31), 0, "MSB of offset should be 0");
             assert_eq!(offset & 0xFF, 0, "lower 8 bits of offset should be 0");
-            self.0 = offset << 2 | (1 << 9) | (self.0 << 23 as u32) >> 23; // with offset extension flag
+            self.0 = offset << 2 | (1 << 9) | (self.0 << 23) >> 23; // with offset extension flag
         }
     }
 
+    /// Sets an offset to the unit like `set_offset()` if `offset` can be stored in the unit.
+    /// Otherwise, returns an error and leaves the unit unchanged.
+    #[inline]
+    pub fn try_set_offset(&mut self, offset: u32) -> Result<(), OffsetError> {
+        if offset >= (1u32 << 29) {
+            return Err(OffsetError::TooLarge);
+        }
+        if offset >= (1u32 << 21) && offset & 0xFF != 0 {
+            return Err(OffsetError::LowBitsSet);
+        }
+        self.set_offset(offset);
+        Ok(())
+    }
+
     /// Sets a `has_leaf` flag to the unit.
     #[inline]
     pub fn set_has_leaf(&mut self, has_leaf: bool) {
@@ -122,12 +177,27 @@ impl Unit {
     }
 
     /// Sets a value to the unit.
+    ///
+    /// **The MSB of `value` is silently dropped**, so `value` should be less than `1 << 31`. Use
+    /// `try_set_value()` to validate the value.
     #[inline]
     pub fn set_value(&mut self, value: u32) {
         self.0 = value | 1 << 31
     }
 
+    /// Sets a value to the unit if `value` can be represented as a 31 bits unsigned integer.
+    /// Otherwise, returns an error and leaves the unit unchanged.
+    #[inline]
+    pub fn try_set_value(&mut self, value: u32) -> Result<(), ValueOutOfRange> {
+        if value >= (1 << 31) {
+            return Err(ValueOutOfRange { value });
+        }
+        self.set_value(value);
+        Ok(())
+    }
+
     /// Returns a string representation of the unit.
+    #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
         if self.is_leaf() {
             // leaf node
@@ -158,6 +228,7 @@ impl std::fmt::Debug for Unit {
 
 #[cfg(test)]
 mod tests {
+    use crate::unit::{OffsetError, ValueOutOfRange};
     use crate::Unit;
 
     #[test]
@@ -175,6 +246,33 @@ mod tests {
         assert_eq!(unit.value(), 0);
     }
 
+    #[test]
+    fn test_unit_try_set_value() {
+        let mut unit = Unit::new();
+        assert_eq!(unit.try_set_value((1 << 31) - 1), Ok(()));
+        assert_eq!(unit.value(), (1 << 31) - 1);
+
+        assert_eq!(
+            unit.try_set_value(1 << 31),
+            Err(ValueOutOfRange { value: 1 << 31 })
+        );
+        assert_eq!(unit.value(), (1 << 31) - 1);
+    }
+
+    #[test]
+    fn test_unit_eq() {
+        let mut unit = Unit::new();
+        unit.set_label(1);
+        assert_eq!(unit, Unit::from_u32(1));
+        assert_ne!(unit, Unit::new());
+
+        let units = [Unit::new(), unit, Unit::from_u32(1)]
+            .iter()
+            .copied()
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(units.len(), 2);
+    }
+
     #[test]
     fn test_label() {
         let unit = Unit::new();
@@ -218,4 +316,21 @@ mod tests {
         unit.set_offset(1 << 28);
         assert_eq!(unit.offset(), 1 << 28);
     }
+
+    #[test]
+    fn test_try_set_offset() {
+        let mut unit = Unit::new();
+        assert_eq!(unit.try_set_offset((1 << 21) - 1), Ok(()));
+        assert_eq!(unit.offset(), (1 << 21) - 1);
+
+        assert_eq!(unit.try_set_offset(1 << 28), Ok(()));
+        assert_eq!(unit.offset(), 1 << 28);
+
+        assert_eq!(unit.try_set_offset(1 << 29), Err(OffsetError::TooLarge));
+        assert_eq!(
+            unit.try_set_offset((1 << 21) + 1),
+            Err(OffsetError::LowBitsSet)
+        );
+        assert_eq!(unit.offset(), 1 << 28);
+    }
 }
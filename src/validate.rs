@@ -0,0 +1,177 @@
+//! Validation of the structure of double arrays.
+
+use crate::unit::{Unit, UnitID};
+use crate::DoubleArray;
+use std::ops::Deref;
+
+/// An error that indicates a violation of an invariant of double arrays.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    /// The double array has no units.
+    Empty,
+    /// The root is a leaf.
+    RootIsLeaf { unit: Unit },
+    /// The node has no children, so no key ends under the node.
+    NoChildren { unit_id: UnitID, unit: Unit },
+    /// The node has a leaf, but the position of the leaf is out of bounds.
+    LeafOutOfBounds { unit_id: UnitID, unit: Unit },
+    /// The node has a leaf, but the unit at the position of the leaf is not a leaf.
+    NotLeaf { unit_id: UnitID, unit: Unit },
+    /// The node is reachable by more than one path, which means that offsets are not unique.
+    Revisited { unit_id: UnitID, unit: Unit },
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ValidationError::Empty => write!(f, "double array has no units"),
+            ValidationError::RootIsLeaf { unit } => write!(f, "root is a leaf: {:?}", unit),
+            ValidationError::NoChildren { unit_id, unit } => {
+                write!(f, "node {} has no children: {:?}", unit_id, unit)
+            }
+            ValidationError::LeafOutOfBounds { unit_id, unit } => {
+                write!(f, "leaf of node {} is out of bounds: {:?}", unit_id, unit)
+            }
+            ValidationError::NotLeaf { unit_id, unit } => {
+                write!(f, "unit {} should be a leaf: {:?}", unit_id, unit)
+            }
+            ValidationError::Revisited { unit_id, unit } => {
+                write!(
+                    f,
+                    "node {} is reachable by more than one path: {:?}",
+                    unit_id, unit
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl<T, const TERM: u8> DoubleArray<T, TERM>
+where
+    T: Deref<Target = [u8]>,
+{
+    /// Checks invariants of all nodes reachable from the root, and returns the first violation.
+    /// A double array built by `DoubleArrayBuilder` always passes the checks.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let root = self.unit_at(0).ok_or(ValidationError::Empty)?;
+        if root.is_leaf() {
+            return Err(ValidationError::RootIsLeaf { unit: root });
+        }
+
+        let mut visited = vec![false; self.num_units()];
+        let mut stack = vec![0 as UnitID];
+        while let Some(unit_id) = stack.pop() {
+            let unit = self.get_unit(unit_id).unwrap();
+            if visited[unit_id] {
+                return Err(ValidationError::Revisited { unit_id, unit });
+            }
+            visited[unit_id] = true;
+
+            if unit.has_leaf() {
+                let leaf_pos = (unit.offset() ^ unit_id as u32) as UnitID;
+                match self.unit_at(leaf_pos) {
+                    None => return Err(ValidationError::LeafOutOfBounds { unit_id, unit }),
+                    Some(leaf) if !leaf.is_leaf() => {
+                        return Err(ValidationError::NotLeaf {
+                            unit_id: leaf_pos,
+                            unit: leaf,
+                        })
+                    }
+                    _ => {}
+                }
+            }
+
+            let num_stack = stack.len();
+            stack.extend(
+                self.children(unit_id)
+                    .filter(|&(label, _)| label != 0)
+                    .map(|(_, child_pos)| child_pos),
+            );
+            if !unit.has_leaf() && stack.len() == num_stack {
+                return Err(ValidationError::NoChildren { unit_id, unit });
+            }
+        }
+        Ok(())
+    }
+
+    /// Panics with the first violation found by `validate()` if debug assertions or the
+    /// `debug_checks` feature are enabled. Otherwise, does nothing.
+    pub fn debug_assert_valid(&self) {
+        #[cfg(any(debug_assertions, feature = "debug_checks"))]
+        if let Err(err) = self.validate() {
+            panic!("invalid double array: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::DoubleArrayBuilder;
+    use crate::unit::Unit;
+    use crate::validate::ValidationError;
+    use crate::DoubleArray;
+
+    #[test]
+    fn test_validate() {
+        let keyset = &[("a", 0), ("ab", 1), ("abc", 2), ("b", 3)];
+        let da = DoubleArray::new(DoubleArrayBuilder::build(keyset).unwrap());
+        assert_eq!(da.validate(), Ok(()));
+        da.debug_assert_valid();
+
+        let da = DoubleArray::new(vec![]);
+        assert_eq!(da.validate(), Err(ValidationError::Empty));
+
+        // the root is a leaf
+        let root = 0x8000_0000u32;
+        let da = DoubleArray::new(root.to_le_bytes().to_vec());
+        assert_eq!(
+            da.validate(),
+            Err(ValidationError::RootIsLeaf {
+                unit: Unit::from_u32(root)
+            })
+        );
+
+        // the root has a leaf at 1, but the unit at 1 is not a leaf
+        let root = (1u32 << 10) | (1 << 8);
+        let mut bytes = root.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        let da = DoubleArray::new(bytes);
+        assert_eq!(
+            da.validate(),
+            Err(ValidationError::NotLeaf {
+                unit_id: 1,
+                unit: Unit::new()
+            })
+        );
+
+        // the root has no children
+        let da = DoubleArray::new(0u32.to_le_bytes().to_vec());
+        assert_eq!(
+            da.validate(),
+            Err(ValidationError::NoChildren {
+                unit_id: 0,
+                unit: Unit::new()
+            })
+        );
+
+        // the child of the root by 'a' is the root itself
+        let root = (b'a' as u32) << 10 | b'a' as u32;
+        let da = DoubleArray::new(root.to_le_bytes().to_vec());
+        assert_eq!(
+            da.validate(),
+            Err(ValidationError::Revisited {
+                unit_id: 0,
+                unit: Unit::from_u32(root)
+            })
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "invalid double array: double array has no units")]
+    fn test_debug_assert_valid() {
+        DoubleArray::new(vec![]).debug_assert_valid();
+    }
+}
@@ -0,0 +1,185 @@
+use crate::builder::DoubleArrayBuilder;
+use crate::DoubleArray;
+use std::ops::Deref;
+
+/// A double array trie whose values are stored in an external value table.
+///
+/// The trie itself only stores a dense leaf ordinal for each key, and the ordinal is used as an
+/// index of the value table. It removes the 31 bits limitation of values.
+#[derive(Clone)]
+pub struct DoubleArrayWithValues<T, V>
+where
+    T: Deref<Target = [u8]>,
+{
+    pub double_array: DoubleArray<T>,
+    pub values: Vec<V>,
+}
+
+impl<T, V> DoubleArrayWithValues<T, V>
+where
+    T: Deref<Target = [u8]>,
+{
+    /// Creates a new `DoubleArrayWithValues` with a byte slice and a value table built by
+    /// `DoubleArrayBuilder::build_with_values()`.
+    pub fn new(bytes: T, values: Vec<V>) -> Self {
+        Self {
+            double_array: DoubleArray::new(bytes),
+            values,
+        }
+    }
+
+    /// Finds a value associated with a `key`.
+    pub fn exact_match_search<K>(&self, key: K) -> Option<&V>
+    where
+        K: AsRef<[u8]>,
+    {
+        let ordinal = self.double_array.exact_match_search(key)?;
+        self.values.get(ordinal as usize)
+    }
+
+    /// Returns a reference to the value associated with a `key`. This is an alias of
+    /// `exact_match_search()` following the naming of maps.
+    pub fn get<K>(&self, key: K) -> Option<&V>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.exact_match_search(key)
+    }
+
+    /// Finds all values and it's key length which have a common prefix with a `key`.
+    pub fn common_prefix_search<'b, K>(
+        &'b self,
+        key: &'b K,
+    ) -> impl Iterator<Item = (&'b V, usize)> + 'b
+    where
+        K: AsRef<[u8]>,
+        K: ?Sized,
+    {
+        self.double_array
+            .common_prefix_search(key)
+            .filter_map(move |(ordinal, len)| Some((self.values.get(ordinal as usize)?, len)))
+    }
+}
+
+/// A map from keys to values built by `DoubleArrayMapBuilder`. It's the same as
+/// `DoubleArrayWithValues`, whose double array maps keys to dense indices of the values.
+pub type DoubleArrayMap<T, V> = DoubleArrayWithValues<T, V>;
+
+/// A builder of `DoubleArrayMap` which accepts keys in any order.
+#[derive(Debug, Clone)]
+pub struct DoubleArrayMapBuilder<V>
+where
+    V: Copy,
+{
+    entries: Vec<(Vec<u8>, V)>,
+}
+
+impl<V> Default for DoubleArrayMapBuilder<V>
+where
+    V: Copy,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> DoubleArrayMapBuilder<V>
+where
+    V: Copy,
+{
+    /// Constructs a new empty `DoubleArrayMapBuilder`.
+    pub fn new() -> Self {
+        Self { entries: vec![] }
+    }
+
+    /// Adds a `key` and its associated `value`.
+    pub fn insert<K>(&mut self, key: K, value: V)
+    where
+        K: AsRef<[u8]>,
+    {
+        self.entries.push((key.as_ref().to_vec(), value));
+    }
+
+    /// Builds a `DoubleArrayMap` whose indices are assigned in the byte order of keys. Returns
+    /// `None` if keys are duplicated or the build failed.
+    pub fn build(mut self) -> Option<DoubleArrayMap<Vec<u8>, V>> {
+        self.entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let (da_bytes, values) = DoubleArrayBuilder::build_with_values(&self.entries)?;
+        Some(DoubleArrayMap::new(da_bytes, values))
+    }
+}
+
+impl DoubleArrayBuilder {
+    /// Builds a double-array trie which stores leaf ordinals instead of values, and returns it
+    /// with a value table indexed by the ordinals. Otherwise, returns `None`.
+    /// The `keyset` must be sorted.
+    pub fn build_with_values<K, V>(keyset: &[(K, V)]) -> Option<(Vec<u8>, Vec<V>)>
+    where
+        K: AsRef<[u8]>,
+        V: Clone,
+    {
+        if keyset.len() >= (1 << 31) {
+            return None;
+        }
+        let ordinals = keyset
+            .iter()
+            .zip(0..)
+            .map(|((key, _), ordinal)| (key.as_ref(), ordinal))
+            .collect::<Vec<(&[u8], u32)>>();
+        let da_bytes = Self::build(&ordinals)?;
+        let values = keyset.iter().map(|(_, value)| value.clone()).collect();
+
+        Some((da_bytes, values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::DoubleArrayBuilder;
+    use crate::values::{DoubleArrayMapBuilder, DoubleArrayWithValues};
+
+    #[test]
+    fn test_build_search_with_values() {
+        let keyset = &[
+            ("a".as_bytes(), u32::MAX),
+            ("ab".as_bytes(), 1 << 31),
+            ("abc".as_bytes(), 0),
+            ("b".as_bytes(), (1 << 31) + 1),
+        ];
+
+        let (da_bytes, values) = DoubleArrayBuilder::build_with_values(keyset).unwrap();
+        let da = DoubleArrayWithValues::new(da_bytes, values);
+
+        for (key, value) in keyset {
+            assert_eq!(da.exact_match_search(key), Some(value));
+        }
+        assert_eq!(da.exact_match_search("aa".as_bytes()), None);
+        assert_eq!(da.exact_match_search("c".as_bytes()), None);
+
+        assert_eq!(
+            da.common_prefix_search("abcd".as_bytes())
+                .collect::<Vec<_>>(),
+            vec![(&u32::MAX, 1), (&(1 << 31), 2), (&0, 3)]
+        );
+    }
+
+    #[test]
+    fn test_build_map() {
+        let mut builder = DoubleArrayMapBuilder::new();
+        builder.insert("b", 2u16);
+        builder.insert("a", 0u16);
+        builder.insert("ab", 1u16);
+
+        let map = builder.build().unwrap();
+        assert_eq!(map.values, vec![0, 1, 2]);
+        assert_eq!(map.get("a"), Some(&0));
+        assert_eq!(map.get("ab"), Some(&1));
+        assert_eq!(map.get("b"), Some(&2));
+        assert_eq!(map.get("c"), None);
+
+        let mut builder = DoubleArrayMapBuilder::new();
+        builder.insert("a", 0u16);
+        builder.insert("a", 1u16);
+        assert!(builder.build().is_none());
+    }
+}
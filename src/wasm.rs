@@ -0,0 +1,52 @@
+//! Bindings for WebAssembly via `wasm-bindgen`.
+
+use crate::DoubleArray;
+use wasm_bindgen::prelude::*;
+
+/// A double array trie that can be used from JavaScript.
+#[wasm_bindgen]
+pub struct WasmDoubleArray {
+    double_array: DoubleArray<Vec<u8>>,
+}
+
+#[wasm_bindgen]
+impl WasmDoubleArray {
+    /// Creates a new `WasmDoubleArray` with bytes of a double array such as a `Uint8Array`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(bytes: &[u8]) -> Result<WasmDoubleArray, JsValue> {
+        match DoubleArray::from_bytes(bytes.to_vec()) {
+            Ok(double_array) => Ok(Self { double_array }),
+            Err(err) => Err(JsValue::from_str(&err.to_string())),
+        }
+    }
+
+    /// Finds a value associated with UTF-8 bytes of a `key`.
+    pub fn exact_match_search(&self, key: &str) -> Option<u32> {
+        self.double_array.exact_match_search(key)
+    }
+
+    /// Finds all values which have a common prefix with UTF-8 bytes of a `key`.
+    pub fn common_prefix_search(&self, key: &str) -> Box<[u32]> {
+        self.double_array
+            .common_prefix_search(key)
+            .map(|(value, _)| value)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::DoubleArrayBuilder;
+    use crate::wasm::WasmDoubleArray;
+
+    #[test]
+    fn test_wasm_double_array() {
+        let keyset = &[("a", 0), ("ab", 1), ("b", 2)];
+        let da_bytes = DoubleArrayBuilder::build(keyset).unwrap();
+
+        let da = WasmDoubleArray::new(&da_bytes).ok().unwrap();
+        assert_eq!(da.exact_match_search("ab"), Some(1));
+        assert_eq!(da.exact_match_search("c"), None);
+        assert_eq!(da.common_prefix_search("abc").to_vec(), vec![0, 1]);
+    }
+}
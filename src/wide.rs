@@ -0,0 +1,313 @@
+//! A double array trie with 64-bit units for very large keysets.
+//!
+//! The offsets of 32-bit units are limited to 29 bits, so very large keysets can't be built with
+//! them. 64-bit units have 40-bit offset fields and 32-bit values at the cost of doubled array
+//! size. Note that `DoubleArrayBuilder` addresses at most `1 << 24` blocks of 256 units, so offsets
+//! of built double arrays are limited to 32 bits in practice.
+//!
+//! Bytes of the wide format start with an 8 bytes header (`WIDE_HEADER`) followed by units. The
+//! first byte of the header is not 0, while the first byte of the 32-bit format is always 0 (the
+//! label of the root), so the formats can be distinguished by `is_wide()`.
+
+use crate::unit::UnitID;
+use crate::DoubleArrayError;
+use std::convert::TryInto;
+use std::ops::Deref;
+
+/// The header of the wide format.
+pub const WIDE_HEADER: [u8; 8] = *b"yada64\0\0";
+
+/// The size of `Unit64` (8).
+pub const WIDE_UNIT_SIZE: usize = std::mem::size_of::<u64>();
+
+/// Returns true if `bytes` starts with the header of the wide format.
+pub fn is_wide(bytes: &[u8]) -> bool {
+    bytes.starts_with(&WIDE_HEADER)
+}
+
+/// A 64-bit unit which represents one node of a wide double array trie.
+///
+/// The bit layout of a non-leaf node:
+///
+/// ```text
+///   0..8     LABEL      8-bits value that represents a label of the double array node.
+///   8        HAS_LEAF   1-bit flag that indicates whether the node has leaf nodes or not.
+///   9..49    OFFSET     40-bits value that represents an offset of the double array node.
+///   63       IS_LEAF    1-bit flag that is always 0 in this case.
+/// ```
+///
+/// The bit layout of a leaf node:
+///
+/// ```text
+///   0..32    VALUE      32-bits value that represents a value of the double array node.
+///   63       IS_LEAF    1-bit flag that is always 1 in this case.
+/// ```
+#[derive(Copy, Clone, Default, PartialEq, Eq, Hash, Debug)]
+pub struct Unit64(u64);
+
+impl Unit64 {
+    /// Creates a new Unit64.
+    #[inline]
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    /// Creates a new Unit64 from `value`.
+    #[inline]
+    pub fn from_u64(value: u64) -> Self {
+        Self(value)
+    }
+
+    /// Returns an internal 64 bit integer.
+    #[inline]
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns true if the unit have a leaf as a child unit. Otherwise, returns false.
+    #[inline]
+    pub fn has_leaf(&self) -> bool {
+        self.0 >> 8 & 1 == 1
+    }
+
+    /// Returns true if the unit is a leaf which have a value. Otherwise, return false.
+    #[inline]
+    pub fn is_leaf(&self) -> bool {
+        self.0 >> 63 == 1
+    }
+
+    /// Returns a 32 bits unsigned integer value associated with the unit.
+    #[inline]
+    pub fn value(&self) -> u32 {
+        self.0 as u32
+    }
+
+    /// Returns a label (<= 255) if the unit is not a leaf. Otherwise, returns an integer value greater
+    /// than 255.
+    #[inline]
+    pub fn label(&self) -> u64 {
+        self.0 & ((1 << 63) | 0xFF)
+    }
+
+    /// Returns an offset value within the unit.
+    #[inline]
+    pub fn offset(&self) -> u64 {
+        (self.0 >> 9) & ((1 << 40) - 1)
+    }
+
+    /// Sets an offset to the unit. `offset` should be a value less than `1 << 40`.
+    #[inline]
+    pub fn set_offset(&mut self, offset: u64) {
+        assert!(offset < (1u64 << 40));
+        self.0 = offset << 9 | (self.0 & 0x1FF);
+    }
+
+    /// Sets a `has_leaf` flag to the unit.
+    #[inline]
+    pub fn set_has_leaf(&mut self, has_leaf: bool) {
+        self.0 = if has_leaf {
+            self.0 | 1 << 8
+        } else {
+            self.0 & !(1 << 8)
+        }
+    }
+
+    /// Sets a label to the unit.
+    #[inline]
+    pub fn set_label(&mut self, label: u8) {
+        self.0 = (self.0 >> 8) << 8 | (label as u64)
+    }
+
+    /// Sets a value to the unit.
+    #[inline]
+    pub fn set_value(&mut self, value: u32) {
+        self.0 = value as u64 | 1 << 63
+    }
+}
+
+/// A double array trie with 64-bit units built by `DoubleArrayBuilder::build_wide()`.
+#[derive(Clone)]
+pub struct DoubleArray64<T>(pub T)
+where
+    T: Deref<Target = [u8]>;
+
+impl<T> DoubleArray64<T>
+where
+    T: Deref<Target = [u8]>,
+{
+    /// Creates a new `DoubleArray64` with a byte slice if the bytes start with the header of the
+    /// wide format and the length of units is a positive multiple of the unit size. Otherwise,
+    /// returns an error.
+    pub fn from_bytes(bytes: T) -> Result<Self, DoubleArrayError> {
+        if !is_wide(&bytes) {
+            return Err(DoubleArrayError::InvalidHeader);
+        }
+        let len = bytes.len() - WIDE_HEADER.len();
+        if len == 0 {
+            return Err(DoubleArrayError::InvalidLength { len: bytes.len() });
+        }
+        let remainder = len % WIDE_UNIT_SIZE;
+        if remainder != 0 {
+            return Err(DoubleArrayError::MisalignedLength {
+                len: bytes.len(),
+                remainder,
+            });
+        }
+        Ok(Self(bytes))
+    }
+
+    /// Finds a value associated with a `key`.
+    pub fn exact_match_search<K>(&self, key: K) -> Option<u32>
+    where
+        K: AsRef<[u8]>,
+    {
+        let mut node_pos = 0 as UnitID;
+        let mut unit = self.get_unit(node_pos)?;
+
+        for &c in key.as_ref() {
+            if c == 0 || unit.is_leaf() {
+                return None;
+            }
+            node_pos = (unit.offset() ^ node_pos as u64 ^ c as u64) as UnitID;
+            unit = self.get_unit(node_pos)?;
+            if unit.label() != c as u64 {
+                return None;
+            }
+        }
+
+        if !unit.has_leaf() {
+            return None;
+        }
+        unit = self.get_unit((unit.offset() ^ node_pos as u64) as UnitID)?;
+        if !unit.is_leaf() {
+            return None;
+        }
+
+        Some(unit.value())
+    }
+
+    /// Finds all values and it's key length which have a common prefix with a `key`.
+    pub fn common_prefix_search<'b, K>(
+        &'b self,
+        key: &'b K,
+    ) -> impl Iterator<Item = (u32, usize)> + 'b
+    where
+        K: AsRef<[u8]>,
+        K: ?Sized,
+    {
+        let key = key.as_ref();
+        let mut node_pos = 0 as UnitID;
+        let mut key_pos = 0;
+
+        std::iter::from_fn(move || {
+            while key_pos < key.len() {
+                let unit = self.get_unit(node_pos)?;
+                let c = key[key_pos];
+                key_pos += 1;
+
+                node_pos = (unit.offset() ^ node_pos as u64 ^ c as u64) as UnitID;
+                let unit = self.get_unit(node_pos)?;
+                if unit.label() != c as u64 {
+                    key_pos = key.len();
+                    return None;
+                }
+                if unit.has_leaf() {
+                    let leaf_pos = unit.offset() ^ node_pos as u64;
+                    let leaf_unit = self.get_unit(leaf_pos as UnitID)?;
+                    return Some((leaf_unit.value(), key_pos));
+                }
+            }
+            None
+        })
+    }
+
+    /// Returns the number of units.
+    pub fn num_units(&self) -> usize {
+        self.0.len().saturating_sub(WIDE_HEADER.len()) / WIDE_UNIT_SIZE
+    }
+
+    /// Returns the unit at `index`, or `None` if `index` is out of bounds.
+    #[inline(always)]
+    pub fn unit_at(&self, index: UnitID) -> Option<Unit64> {
+        let begin = WIDE_HEADER
+            .len()
+            .checked_add(index.checked_mul(WIDE_UNIT_SIZE)?)?;
+        let b = self.0.get(begin..begin.checked_add(WIDE_UNIT_SIZE)?)?;
+        Some(Unit64::from_u64(u64::from_le_bytes(b.try_into().ok()?)))
+    }
+
+    #[inline(always)]
+    fn get_unit(&self, index: UnitID) -> Option<Unit64> {
+        self.unit_at(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::DoubleArrayBuilder;
+    use crate::wide::{is_wide, DoubleArray64, Unit64};
+    use crate::DoubleArrayError;
+
+    #[test]
+    fn test_unit64() {
+        let mut unit = Unit64::new();
+        unit.set_label(b'a');
+        unit.set_has_leaf(true);
+        unit.set_offset((1 << 40) - 1);
+        assert_eq!(unit.label(), b'a' as u64);
+        assert!(unit.has_leaf());
+        assert!(!unit.is_leaf());
+        assert_eq!(unit.offset(), (1 << 40) - 1);
+
+        unit.set_value(u32::MAX);
+        assert!(unit.is_leaf());
+        assert_eq!(unit.value(), u32::MAX);
+        assert!(unit.label() > 255);
+    }
+
+    #[test]
+    fn test_build_wide() {
+        let keyset = &[
+            ("a".as_bytes(), 0),
+            ("ab".as_bytes(), u32::MAX),
+            ("abc".as_bytes(), 2),
+            ("b".as_bytes(), 1 << 31),
+        ];
+        let da_bytes = DoubleArrayBuilder::build_wide(keyset).unwrap();
+        assert!(is_wide(&da_bytes));
+
+        let da = DoubleArray64::from_bytes(da_bytes).unwrap();
+        for (key, value) in keyset {
+            assert_eq!(da.exact_match_search(key), Some(*value));
+        }
+        assert_eq!(da.exact_match_search("abcd"), None);
+        assert_eq!(da.exact_match_search("c"), None);
+        assert_eq!(
+            da.common_prefix_search("abcd").collect::<Vec<_>>(),
+            vec![(0, 1), (u32::MAX, 2), (2, 3)]
+        );
+        assert_eq!(
+            da.common_prefix_search("bc").collect::<Vec<_>>(),
+            vec![(1 << 31, 1)]
+        );
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let da_bytes = DoubleArrayBuilder::build(&[("a", 0)]).unwrap();
+        assert!(!is_wide(&da_bytes));
+        assert_eq!(
+            DoubleArray64::from_bytes(da_bytes).err(),
+            Some(DoubleArrayError::InvalidHeader)
+        );
+
+        let da_bytes = DoubleArrayBuilder::build_wide(&[("a", 0)]).unwrap();
+        assert_eq!(
+            DoubleArray64::from_bytes(&da_bytes[..12]).err(),
+            Some(DoubleArrayError::MisalignedLength {
+                len: 12,
+                remainder: 4
+            })
+        );
+    }
+}